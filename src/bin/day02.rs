@@ -24,6 +24,7 @@ impl FromStr for Outcome {
 }
 
 #[derive(Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
 enum RPS {
     Rock,
     Paper,
@@ -1,6 +1,8 @@
+use std::collections::BTreeSet;
 use std::convert::Infallible;
 use std::str::FromStr;
 
+use adventofcode2022::interval::IntervalSet;
 use adventofcode2022::read_input_lines_as;
 
 #[derive(Clone, Copy, Debug)]
@@ -28,7 +30,6 @@ impl ElfRange {
 
     fn overlaps(&self, other: &ElfRange) -> bool {
         self.lower <= other.upper && self.upper >= other.lower
-            || other.lower <= self.upper && other.upper >= self.lower
     }
 }
 
@@ -57,6 +58,103 @@ impl ElfPair {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+struct Conflict {
+    first: usize,
+    second: usize,
+    containing: bool,
+}
+
+#[derive(Clone, Debug)]
+struct Cluster {
+    lower: u32,
+    upper: u32,
+    elves: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct ConflictReport {
+    conflicts: Vec<Conflict>,
+    clusters: Vec<Cluster>,
+    max_coverage: usize,
+    max_coverage_at: u32,
+    uncovered: IntervalSet<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    // Starts sort before ends at the same section since ranges are inclusive.
+    Start,
+    End,
+}
+
+fn analyze_conflicts(elves: &[ElfRange]) -> ConflictReport {
+    let mut events = elves
+        .iter()
+        .enumerate()
+        .flat_map(|(ix, it)| [(it.lower, Event::Start, ix), (it.upper, Event::End, ix)])
+        .collect::<Vec<_>>();
+    events.sort();
+
+    let mut active = BTreeSet::new();
+    let mut conflicts = vec![];
+    let mut clusters = vec![];
+    let mut current: Option<Cluster> = None;
+    let mut max_coverage = 0;
+    let mut max_coverage_at = 0;
+    let mut covered = IntervalSet::new();
+
+    for (section, event, ix) in events {
+        match event {
+            Event::Start => {
+                for &other in &active {
+                    let (a, b) = (&elves[other], &elves[ix]);
+                    conflicts.push(Conflict {
+                        first: other,
+                        second: ix,
+                        containing: a.contains(b) || b.contains(a),
+                    });
+                }
+                active.insert(ix);
+                if active.len() > max_coverage {
+                    max_coverage = active.len();
+                    max_coverage_at = section;
+                }
+                current
+                    .get_or_insert(Cluster {
+                        lower: section,
+                        upper: section,
+                        elves: vec![],
+                    })
+                    .elves
+                    .push(ix);
+            }
+            Event::End => {
+                active.remove(&ix);
+                covered.insert(elves[ix].lower..=elves[ix].upper);
+                if active.is_empty() {
+                    let mut cluster = current.take().unwrap();
+                    cluster.upper = section;
+                    clusters.push(cluster);
+                }
+            }
+        }
+    }
+
+    let uncovered = match (covered.ranges().first(), covered.ranges().last()) {
+        (Some(first), Some(last)) => covered.complement(*first.start()..=*last.end()),
+        _ => IntervalSet::new(),
+    };
+
+    ConflictReport {
+        conflicts,
+        clusters,
+        max_coverage,
+        max_coverage_at,
+        uncovered,
+    }
+}
+
 fn part1() {
     let input = read_input_lines_as::<ElfPair>(4);
     let result = input.iter().filter(|it| it.redundant()).count();
@@ -72,7 +170,57 @@ fn part2() {
     println!("Part 2: {result}");
 }
 
+fn conflicts() {
+    let elves = read_input_lines_as::<ElfPair>(4)
+        .into_iter()
+        .flat_map(|it| [it.first, it.second])
+        .collect::<Vec<_>>();
+    let report = analyze_conflicts(&elves);
+    let containing = report.conflicts.iter().filter(|it| it.containing).count();
+    println!(
+        "Conflicts: {} overlapping pairs, {containing} containing",
+        report.conflicts.len()
+    );
+    let mut per_elf = vec![0; elves.len()];
+    for conflict in &report.conflicts {
+        per_elf[conflict.first] += 1;
+        per_elf[conflict.second] += 1;
+    }
+    if let Some((ix, count)) = per_elf.iter().enumerate().max_by_key(|(_, it)| **it) {
+        let elf = elves[ix];
+        println!(
+            "Most conflicted: elf {ix} ({}-{}) with {count} conflicts",
+            elf.lower, elf.upper
+        );
+    }
+    println!("Clusters: {}", report.clusters.len());
+    for cluster in report.clusters.iter().filter(|it| it.elves.len() > 1) {
+        println!(
+            "  {}-{}: {} elves",
+            cluster.lower,
+            cluster.upper,
+            cluster.elves.len()
+        );
+    }
+    println!(
+        "Max coverage: {} elves at section {}",
+        report.max_coverage, report.max_coverage_at
+    );
+    println!(
+        "Uncovered: {:?} ({} sections)",
+        report.uncovered.ranges(),
+        report.uncovered.size()
+    );
+}
+
 fn main() {
     part1();
     part2();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|it| it.as_str()).collect::<Vec<_>>()[..] {
+        ["conflicts"] => conflicts(),
+        [] => {}
+        _ => panic!("Usage: day04 [conflicts]"),
+    }
 }
@@ -1,20 +1,37 @@
 use std::convert::Infallible;
+use std::fmt::Display;
 use std::str::FromStr;
+use std::time::Duration;
 
-use adventofcode2022::read_input_lines;
+use adventofcode2022::read_input;
+use itertools::Itertools;
 
-fn initial_stacks() -> Vec<Vec<char>> {
-    vec![
-        vec!['D', 'H', 'N', 'Q', 'T', 'W', 'V', 'B'],
-        vec!['D', 'W', 'B'],
-        vec!['T', 'S', 'Q', 'W', 'J', 'C'],
-        vec!['F', 'J', 'R', 'N', 'Z', 'T', 'P'],
-        vec!['G', 'P', 'V', 'J', 'M', 'S', 'T'],
-        vec!['B', 'W', 'F', 'T', 'N'],
-        vec!['B', 'L', 'D', 'Q', 'F', 'H', 'V', 'N'],
-        vec!['H', 'P', 'F', 'R'],
-        vec!['Z', 'S', 'M', 'B', 'L', 'N', 'P', 'H'],
-    ]
+fn parse_stacks(drawing: &str) -> Vec<Vec<char>> {
+    let mut lines: Vec<Vec<char>> = drawing
+        .lines()
+        .filter(|it| !it.trim().is_empty())
+        .map(|it| it.chars().collect())
+        .collect();
+    let footer = lines
+        .pop()
+        .expect("Stack drawing is missing its numbered footer.");
+    let columns: Vec<usize> = footer
+        .iter()
+        .enumerate()
+        .filter(|(ix, c)| c.is_ascii_digit() && (*ix == 0 || !footer[ix - 1].is_ascii_digit()))
+        .map(|(ix, _)| ix)
+        .collect();
+
+    let mut stacks = vec![vec![]; columns.len()];
+    for line in lines.iter().rev() {
+        for (stack, col) in stacks.iter_mut().zip(&columns) {
+            match line.get(*col) {
+                Some(c) if c.is_ascii_alphanumeric() => stack.push(*c),
+                _ => {}
+            }
+        }
+    }
+    stacks
 }
 
 struct Instruction {
@@ -36,58 +53,238 @@ impl FromStr for Instruction {
     }
 }
 
-impl Instruction {
-    fn apply_9000(&self, to: &mut [Vec<char>]) {
-        for _i in 0..self.quantity {
-            let curr = to[self.from - 1].pop().unwrap();
-            to[self.to - 1].push(curr);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MoveError {
+    InvalidStack(usize),
+    Underflow {
+        stack: usize,
+        requested: usize,
+        available: usize,
+    },
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::InvalidStack(index) => write!(f, "no stack numbered {index}"),
+            MoveError::Underflow {
+                stack,
+                requested,
+                available,
+            } => write!(
+                f,
+                "cannot take {requested} crates from stack {stack}, which only has {available}"
+            ),
         }
     }
+}
 
-    fn apply_9001(&self, to: &mut [Vec<char>]) {
-        let mut buf = vec![];
-        for _i in 0..self.quantity {
-            let curr = to[self.from - 1].pop().unwrap();
-            buf.push(curr);
-        }
+// A crane removes `quantity` crates from a stack and returns them in the order they end up
+// being pushed onto the destination, bottom first.
+trait Crane {
+    fn name(&self) -> String;
+    fn pick(&self, stack: &mut Vec<char>, quantity: usize) -> Vec<char>;
+}
+
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".to_string()
+    }
+
+    fn pick(&self, stack: &mut Vec<char>, quantity: usize) -> Vec<char> {
+        stack.drain((stack.len() - quantity)..).rev().collect()
+    }
+}
+
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".to_string()
+    }
+
+    fn pick(&self, stack: &mut Vec<char>, quantity: usize) -> Vec<char> {
+        stack.drain((stack.len() - quantity)..).collect()
+    }
+}
+
+// Moves at most `capacity` crates per lift, keeping the order within each lift.
+struct LimitedCrane {
+    capacity: usize,
+}
+
+impl Crane for LimitedCrane {
+    fn name(&self) -> String {
+        format!("Limited crane (capacity {})", self.capacity)
+    }
+
+    fn pick(&self, stack: &mut Vec<char>, quantity: usize) -> Vec<char> {
+        let lifted: Vec<char> = stack.drain((stack.len() - quantity)..).collect();
+        lifted
+            .rchunks(self.capacity.max(1))
+            .flat_map(|it| it.iter().copied())
+            .collect()
+    }
+}
+
+// Pulls crates out from the bottom of the stack one at a time.
+struct BottomCrane;
 
-        for _i in 0..self.quantity {
-            to[self.to - 1].push(buf.pop().unwrap());
+impl Crane for BottomCrane {
+    fn name(&self) -> String {
+        "Bottom crane".to_string()
+    }
+
+    fn pick(&self, stack: &mut Vec<char>, quantity: usize) -> Vec<char> {
+        stack.drain(..quantity).collect()
+    }
+}
+
+impl Instruction {
+    fn apply(&self, crane: &dyn Crane, stacks: &mut [Vec<char>]) -> Result<(), MoveError> {
+        for index in [self.from, self.to] {
+            if index == 0 || index > stacks.len() {
+                return Err(MoveError::InvalidStack(index));
+            }
+        }
+        let quantity = self.quantity as usize;
+        let available = stacks[self.from - 1].len();
+        if quantity > available {
+            return Err(MoveError::Underflow {
+                stack: self.from,
+                requested: quantity,
+                available,
+            });
         }
+        let lifted = crane.pick(&mut stacks[self.from - 1], quantity);
+        stacks[self.to - 1].extend(lifted);
+        Ok(())
+    }
+}
+
+// Applies every instruction to `stacks` in place, stopping at the first failing step.
+fn apply_all(
+    crane: &dyn Crane,
+    stacks: &mut [Vec<char>],
+    instructions: &[Instruction],
+) -> Result<(), (usize, MoveError)> {
+    for (step, ins) in instructions.iter().enumerate() {
+        ins.apply(crane, stacks).map_err(|err| (step + 1, err))?;
+    }
+    Ok(())
+}
+
+// Returns the stacks after every step, starting with the initial state, or the failing step
+// number and its error.
+fn run_crane(
+    crane: &dyn Crane,
+    stacks: &[Vec<char>],
+    instructions: &[Instruction],
+) -> Result<Vec<Vec<Vec<char>>>, (usize, MoveError)> {
+    let mut trace = vec![stacks.to_vec()];
+    let mut curr = stacks.to_vec();
+    for (step, ins) in instructions.iter().enumerate() {
+        ins.apply(crane, &mut curr).map_err(|err| (step + 1, err))?;
+        trace.push(curr.clone());
+    }
+    Ok(trace)
+}
+
+fn render_stacks(stacks: &[Vec<char>]) -> String {
+    let height = stacks.iter().map(|it| it.len()).max().unwrap_or(0);
+    let mut lines = vec![];
+    for level in (0..height).rev() {
+        let line = stacks
+            .iter()
+            .map(|it| match it.get(level) {
+                Some(c) => format!("[{c}]"),
+                None => "   ".to_string(),
+            })
+            .join(" ");
+        lines.push(line.trim_end().to_string());
     }
+    lines.push(
+        (1..=stacks.len())
+            .map(|it| format!(" {it} "))
+            .join(" ")
+            .trim_end()
+            .to_string(),
+    );
+    lines.join("\n")
 }
 
-fn read_instructions() -> Vec<Instruction> {
-    read_input_lines(5, false)
-        .into_iter()
-        .filter(|it| it.starts_with("move"))
+fn animate(trace: &[Vec<Vec<char>>], frame_delay: Duration) {
+    for (step, stacks) in trace.iter().enumerate() {
+        print!("\x1b[2J\x1b[H");
+        println!("Step {step}/{}", trace.len() - 1);
+        println!("{}", render_stacks(stacks));
+        std::thread::sleep(frame_delay);
+    }
+}
+
+fn parse_input(input: &str) -> (Vec<Vec<char>>, Vec<Instruction>) {
+    let (drawing, moves) = input
+        .split_once("\n\n")
+        .expect("Input should separate the drawing from the moves with a blank line.");
+    let instructions = moves
+        .lines()
+        .filter(|it| !it.is_empty())
         .map(|it| it.parse().unwrap())
-        .collect()
+        .collect();
+    (parse_stacks(drawing), instructions)
 }
 
 fn form_top_string(stacks: &[Vec<char>]) -> String {
-    stacks.iter().map(|it| it.last().unwrap()).collect()
+    stacks
+        .iter()
+        .map(|it| it.last().copied().unwrap_or(' '))
+        .collect()
 }
 
-fn part1() {
-    let mut stacks = initial_stacks();
-    let instructions = read_instructions();
-    for ins in instructions {
-        ins.apply_9000(&mut stacks);
+fn run_and_report(label: &str, crane: &dyn Crane, input: &str) {
+    let (mut stacks, instructions) = parse_input(input);
+    match apply_all(crane, &mut stacks, &instructions) {
+        Ok(()) => println!("{label}: {}", form_top_string(&stacks)),
+        Err((step, err)) => println!("{label}: step {step} failed: {err}"),
     }
-    println!("Part 1: {}", form_top_string(&stacks))
 }
 
-fn part2() {
-    let mut stacks = initial_stacks();
-    let instructions = read_instructions();
-    for ins in instructions {
-        ins.apply_9001(&mut stacks);
+fn part1(input: &str) {
+    run_and_report("Part 1", &CrateMover9000, input);
+}
+
+fn part2(input: &str) {
+    run_and_report("Part 2", &CrateMover9001, input);
+}
+
+fn other_cranes(input: &str) {
+    let cranes: Vec<Box<dyn Crane>> = vec![
+        Box::new(LimitedCrane { capacity: 3 }),
+        Box::new(BottomCrane),
+    ];
+    for crane in cranes {
+        run_and_report(&crane.name(), crane.as_ref(), input);
     }
-    println!("Part 2: {}", form_top_string(&stacks))
 }
 
 fn main() {
-    part1();
-    part2();
+    let input = read_input(5);
+    part1(&input);
+    part2(&input);
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|it| it.as_str()).collect_vec()[..] {
+        ["cranes"] => other_cranes(&input),
+        ["--animate"] => {
+            let (stacks, instructions) = parse_input(&input);
+            match run_crane(&CrateMover9001, &stacks, &instructions) {
+                Ok(trace) => animate(&trace, Duration::from_millis(50)),
+                Err((step, err)) => println!("Cannot animate: step {step} failed: {err}"),
+            }
+        }
+        [] => {}
+        _ => panic!("Usage: day05 [cranes | --animate]"),
+    }
 }
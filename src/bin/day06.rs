@@ -1,40 +1,88 @@
-use std::collections::{HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Read};
 
-use adventofcode2022::read_input;
+use adventofcode2022::input_path;
 
-fn find_start_of_uniq_seq(chars: &[char], n: usize) -> usize {
-    let mut buf: VecDeque<char> = VecDeque::new();
+// Returns the position just past every window of `n` distinct symbols, keeping per-symbol
+// counts so each step does constant work. Whitespace in the source is skipped.
+fn find_markers<R: Read>(source: R, n: usize) -> std::io::Result<Vec<usize>> {
+    if n == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "window size must be at least 1",
+        ));
+    }
+    let mut counts = [0usize; 256];
+    let mut window = vec![0u8; n];
+    let mut distinct = 0;
+    let mut seen = 0;
+    let mut markers = vec![];
 
-    for (i, c) in chars.iter().enumerate() {
-        if buf.len() < n {
-            buf.push_back(*c);
-        } else {
-            let uniq: HashSet<char> = HashSet::from_iter(buf.iter().copied());
-            if uniq.len() == n {
-                return i;
+    for byte in BufReader::new(source).bytes() {
+        let byte = byte?;
+        if byte.is_ascii_whitespace() {
+            continue;
+        }
+        let slot = seen % n;
+        if seen >= n {
+            let outgoing = window[slot] as usize;
+            counts[outgoing] -= 1;
+            if counts[outgoing] == 0 {
+                distinct -= 1;
             }
-
-            buf.pop_front();
-            buf.push_back(*c);
+        }
+        window[slot] = byte;
+        counts[byte as usize] += 1;
+        if counts[byte as usize] == 1 {
+            distinct += 1;
+        }
+        seen += 1;
+        if distinct == n {
+            markers.push(seen);
         }
     }
 
-    panic!("didn't find a start of unique sequence");
+    Ok(markers)
+}
+
+fn first_marker(n: usize) -> usize {
+    let input = File::open(input_path(6)).unwrap();
+    *find_markers(input, n)
+        .unwrap()
+        .first()
+        .expect("didn't find a start of unique sequence")
 }
 
 fn part1() {
-    let chars: Vec<char> = read_input(6).trim().chars().collect();
-    let result = find_start_of_uniq_seq(&chars, 4);
+    let result = first_marker(4);
     println!("Part 1: {result}");
 }
 
 fn part2() {
-    let chars: Vec<char> = read_input(6).trim().chars().collect();
-    let result = find_start_of_uniq_seq(&chars, 14);
+    let result = first_marker(14);
     println!("Part 2: {result}");
 }
 
+fn all_markers(sizes: &[usize]) {
+    for &n in sizes {
+        let input = File::open(input_path(6)).unwrap();
+        match find_markers(input, n) {
+            Ok(markers) => println!("Window {n}: {} markers {:?}", markers.len(), markers),
+            Err(err) => println!("Window {n}: {err}"),
+        }
+    }
+}
+
 fn main() {
     part1();
     part2();
+
+    let mut sizes = vec![];
+    for arg in std::env::args().skip(1) {
+        match arg.parse() {
+            Ok(n) => sizes.push(n),
+            Err(err) => println!("Skipping window size {arg:?}: {err}"),
+        }
+    }
+    all_markers(&sizes);
 }
@@ -1,115 +1,445 @@
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::fmt::Display;
 
 use adventofcode2022::read_input_lines;
+use itertools::Itertools;
+
+type NodeId = usize;
+
+#[derive(Debug)]
+enum NodeKind {
+    Dir { children: BTreeMap<String, NodeId> },
+    File { size: usize },
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    parent: Option<NodeId>,
+    kind: NodeKind,
+}
 
 #[derive(Debug)]
-struct File {
-    parent: PathBuf,
-    _filename: String,
-    size: usize,
-}
-
-impl File {
-    fn from_str(s: &str, curr_path: &Path) -> Self {
-        let parts: Vec<&str> = s.split(' ').collect();
-        File {
-            parent: curr_path.to_path_buf(),
-            _filename: parts[1].to_string(),
-            size: parts[0].parse().unwrap(),
+struct FileSystem {
+    nodes: Vec<Node>,
+    sizes: OnceCell<Vec<usize>>,
+}
+
+impl FileSystem {
+    fn new() -> FileSystem {
+        FileSystem {
+            nodes: vec![Node {
+                name: String::new(),
+                parent: None,
+                kind: NodeKind::Dir {
+                    children: BTreeMap::new(),
+                },
+            }],
+            sizes: OnceCell::new(),
         }
     }
-}
 
-fn process_ls(lines: &[String], curr_path: &Path, output: &mut Vec<File>) {
-    if let Some(l) = lines.first() {
-        if l.starts_with('$') {
-            return process_input_lines(lines, curr_path, output);
+    fn root(&self) -> NodeId {
+        0
+    }
+
+    fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    fn is_dir(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].kind, NodeKind::Dir { .. })
+    }
+
+    fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let children = match &self.nodes[id].kind {
+            NodeKind::Dir { children } => Some(children.values().copied()),
+            NodeKind::File { .. } => None,
+        };
+        children.into_iter().flatten()
+    }
+
+    fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        match &self.nodes[id].kind {
+            NodeKind::Dir { children } => children.get(name).copied(),
+            NodeKind::File { .. } => None,
         }
-        if l.starts_with("dir ") {
-            return process_ls(&lines[1..lines.len()], curr_path, output);
+    }
+
+    fn insert(&mut self, parent: NodeId, name: &str, kind: NodeKind) -> NodeId {
+        if let Some(existing) = self.child(parent, name) {
+            return existing;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: Some(parent),
+            kind,
+        });
+        match &mut self.nodes[parent].kind {
+            NodeKind::Dir { children } => {
+                children.insert(name.to_string(), id);
+            }
+            NodeKind::File { .. } => panic!("Cannot add {name} under a file."),
         }
-        output.push(File::from_str(l, curr_path));
-        process_ls(&lines[1..lines.len()], curr_path, output);
+        self.sizes = OnceCell::new();
+        id
+    }
+
+    fn mkdir(&mut self, parent: NodeId, name: &str) -> NodeId {
+        self.insert(
+            parent,
+            name,
+            NodeKind::Dir {
+                children: BTreeMap::new(),
+            },
+        )
+    }
+
+    fn add_file(&mut self, parent: NodeId, name: &str, size: usize) -> NodeId {
+        self.insert(parent, name, NodeKind::File { size })
     }
-}
 
-fn process_input_lines(lines: &[String], curr_path: &Path, output: &mut Vec<File>) {
-    if let Some(l) = lines.first() {
-        if !l.starts_with('$') {
-            panic!("Found non-command in command context: {l}");
+    fn lookup(&self, path: &str) -> Option<NodeId> {
+        path.split('/')
+            .filter(|it| !it.is_empty())
+            .try_fold(self.root(), |curr, name| self.child(curr, name))
+    }
+
+    fn path(&self, id: NodeId) -> String {
+        let mut names = vec![];
+        let mut curr = Some(id);
+        while let Some(node) = curr.filter(|it| *it != self.root()) {
+            names.push(self.nodes[node].name.as_str());
+            curr = self.parent(node);
         }
-        if l.starts_with("$ cd ") {
-            let dirname = &l["$ cd ".len()..l.len()];
-            if dirname == ".." {
-                return process_input_lines(
-                    &lines[1..lines.len()],
-                    curr_path.parent().unwrap(),
-                    output,
-                );
-            } else {
-                return process_input_lines(
-                    &lines[1..lines.len()],
-                    &curr_path.join(dirname),
-                    output,
-                );
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    // Children are always allocated after their parents, so walking the arena backwards
+    // accumulates every subtree before its parent is reached.
+    fn size(&self, id: NodeId) -> usize {
+        self.sizes.get_or_init(|| {
+            let mut sizes: Vec<usize> = self
+                .nodes
+                .iter()
+                .map(|it| match it.kind {
+                    NodeKind::File { size } => size,
+                    NodeKind::Dir { .. } => 0,
+                })
+                .collect();
+            for (id, node) in self.nodes.iter().enumerate().rev() {
+                if let Some(parent) = node.parent {
+                    sizes[parent] += sizes[id];
+                }
+            }
+            sizes
+        })[id]
+    }
+
+    fn is_ancestor(&self, ancestor: NodeId, id: NodeId) -> bool {
+        let mut curr = self.parent(id);
+        while let Some(node) = curr {
+            if node == ancestor {
+                return true;
             }
+            curr = self.parent(node);
+        }
+        false
+    }
+
+    fn dirs(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len()).filter(|it| self.is_dir(*it))
+    }
+
+    fn render_tree(&self, id: NodeId) -> String {
+        let mut lines = vec![];
+        let mut stack = vec![(id, 0)];
+        while let Some((curr, depth)) = stack.pop() {
+            let node = &self.nodes[curr];
+            let name = if curr == self.root() { "/" } else { &node.name };
+            let detail = match node.kind {
+                NodeKind::Dir { .. } => format!("dir, {}", self.size(curr)),
+                NodeKind::File { size } => format!("file, {size}"),
+            };
+            lines.push(format!("{}- {name} ({detail})", "  ".repeat(depth)));
+            let children: Vec<NodeId> = self.children(curr).collect();
+            stack.extend(children.into_iter().rev().map(|it| (it, depth + 1)));
         }
-        if l.starts_with("$ ls") {
-            return process_ls(&lines[1..lines.len()], curr_path, output);
+        lines.join("\n")
+    }
+
+    fn render_du(&self, id: NodeId) -> String {
+        let mut lines = vec![];
+        let mut stack = vec![id];
+        while let Some(curr) = stack.pop() {
+            lines.push(format!("{}\t{}", self.size(curr), self.path(curr)));
+            stack.extend(self.children(curr).filter(|it| self.is_dir(*it)));
         }
-        panic!("Unknown command: {l}");
+        lines.reverse();
+        lines.join("\n")
     }
 }
 
-fn process_input() -> Vec<File> {
-    let lines = read_input_lines(7, false);
-    let mut files = vec![];
-    process_input_lines(&lines, Path::new("/"), &mut files);
-    files
+#[derive(Debug, PartialEq, Eq)]
+enum ParseErrorKind {
+    UnknownCommand(String),
+    OutputOutsideListing(String),
+    MalformedEntry(String),
+    CdIntoFile(String),
+    ConflictingEntry(String),
 }
 
-fn calc_sizes_by_dir(files: &[File]) -> HashMap<&Path, usize> {
-    let mut sizes_by_dir: HashMap<&Path, usize> = HashMap::new();
-    for file in files.iter() {
-        let mut p: &Path = &file.parent;
-        loop {
-            *sizes_by_dir.entry(p).or_insert(0) += file.size;
-            if p == Path::new("/") {
-                break;
+#[derive(Debug, PartialEq, Eq)]
+struct ParseError {
+    line: usize,
+    kind: ParseErrorKind,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ParseErrorKind::UnknownCommand(l) => write!(f, "unknown command {l:?}"),
+            ParseErrorKind::OutputOutsideListing(l) => {
+                write!(f, "output {l:?} does not follow an ls")
+            }
+            ParseErrorKind::MalformedEntry(l) => write!(f, "malformed ls entry {l:?}"),
+            ParseErrorKind::CdIntoFile(name) => write!(f, "cannot cd into file {name:?}"),
+            ParseErrorKind::ConflictingEntry(name) => {
+                write!(f, "repeated ls disagrees about {name:?}")
+            }
+        }
+    }
+}
+
+enum ParseState {
+    Command,
+    Listing { dir: NodeId, repeated: bool },
+}
+
+fn parse_entry(
+    fs: &mut FileSystem,
+    dir: NodeId,
+    entry: &str,
+    repeated: bool,
+) -> Result<(), ParseErrorKind> {
+    let malformed = || ParseErrorKind::MalformedEntry(entry.to_string());
+    let (info, name) = entry.split_once(' ').ok_or_else(malformed)?;
+    if name.is_empty() || name.contains('/') {
+        return Err(malformed());
+    }
+    let size = if info == "dir" {
+        None
+    } else {
+        Some(info.parse::<usize>().map_err(|_| malformed())?)
+    };
+
+    let conflict = || ParseErrorKind::ConflictingEntry(name.to_string());
+    match fs.child(dir, name) {
+        Some(existing) => match (&fs.nodes[existing].kind, size) {
+            (NodeKind::File { size: known }, Some(size)) if *known == size => Ok(()),
+            (NodeKind::Dir { .. }, None) => Ok(()),
+            _ => Err(conflict()),
+        },
+        None if repeated => Err(conflict()),
+        None => {
+            match size {
+                Some(size) => fs.add_file(dir, name, size),
+                None => fs.mkdir(dir, name),
+            };
+            Ok(())
+        }
+    }
+}
+
+fn parse_transcript(lines: &[String]) -> Result<FileSystem, ParseError> {
+    let mut fs = FileSystem::new();
+    let mut listed = HashSet::new();
+    let mut cwd = fs.root();
+    let mut state = ParseState::Command;
+
+    for (ix, l) in lines.iter().enumerate() {
+        if l.trim().is_empty() {
+            continue;
+        }
+        let error = |kind| ParseError { line: ix + 1, kind };
+        if let Some(command) = l.strip_prefix("$ ") {
+            state = ParseState::Command;
+            let mut words = command.split(' ');
+            match (words.next(), words.next(), words.next()) {
+                (Some("cd"), Some(dirname), None) => {
+                    cwd = match dirname {
+                        ".." => fs.parent(cwd).unwrap_or(fs.root()),
+                        "/" => fs.root(),
+                        _ => match fs.child(cwd, dirname) {
+                            Some(dir) if fs.is_dir(dir) => dir,
+                            Some(_) => {
+                                return Err(error(ParseErrorKind::CdIntoFile(dirname.to_string())))
+                            }
+                            None => fs.mkdir(cwd, dirname),
+                        },
+                    };
+                }
+                (Some("ls"), None, None) => {
+                    state = ParseState::Listing {
+                        dir: cwd,
+                        repeated: !listed.insert(cwd),
+                    };
+                }
+                _ => return Err(error(ParseErrorKind::UnknownCommand(l.clone()))),
+            }
+            continue;
+        }
+
+        match state {
+            ParseState::Command => {
+                return Err(error(ParseErrorKind::OutputOutsideListing(l.clone())))
+            }
+            ParseState::Listing { dir, repeated } => {
+                parse_entry(&mut fs, dir, l, repeated).map_err(error)?
             }
-            p = p.parent().unwrap();
         }
     }
-    sizes_by_dir
+
+    Ok(fs)
+}
+
+fn process_input() -> FileSystem {
+    let lines = read_input_lines(7, true);
+    match parse_transcript(&lines) {
+        Ok(fs) => fs,
+        Err(err) => panic!("Invalid terminal log: {err}"),
+    }
+}
+
+fn calc_sizes_by_dir(fs: &FileSystem) -> HashMap<NodeId, usize> {
+    fs.dirs().map(|it| (it, fs.size(it))).collect()
 }
 
 fn part1() {
-    let files = process_input();
-    let result: usize = calc_sizes_by_dir(&files)
+    let fs = process_input();
+    let result: usize = calc_sizes_by_dir(&fs)
         .values()
         .filter(|it| **it <= 100000)
         .sum();
     println!("Part 1: {result}");
 }
 
-fn part2() {
-    let files = process_input();
-    let dir_sizes = calc_sizes_by_dir(&files);
-    let total_file_size = dir_sizes[Path::new("/")];
-    let space_needed = 30000000 - (70000000 - total_file_size);
-    let mut best_dir = Path::new("/");
-    let mut best_size = total_file_size;
-    for (dir, size) in dir_sizes.iter() {
-        if *size >= space_needed && *size < best_size {
-            best_dir = *dir;
-            best_size = *size;
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct CleanupPlan {
+    freed: usize,
+    dirs: Vec<NodeId>,
+}
+
+struct CleanupSearch<'a> {
+    fs: &'a FileSystem,
+    candidates: Vec<(NodeId, usize)>,
+    needed: usize,
+    max_dirs: usize,
+    max_plans: usize,
+    best: BinaryHeap<CleanupPlan>,
+}
+
+impl CleanupSearch<'_> {
+    // Candidates are sorted largest first, so the directory that pushes a plan over the
+    // threshold is always its smallest one and the plan can't drop any of its members.
+    fn search(&mut self, start: usize, chosen: &mut Vec<NodeId>, freed: usize) {
+        let slots = self.max_dirs - chosen.len();
+        for ix in start..self.candidates.len() {
+            let (dir, size) = self.candidates[ix];
+            if freed + size * slots < self.needed {
+                break;
+            }
+            let nested = chosen
+                .iter()
+                .any(|it| self.fs.is_ancestor(*it, dir) || self.fs.is_ancestor(dir, *it));
+            if nested {
+                continue;
+            }
+            chosen.push(dir);
+            if freed + size >= self.needed {
+                self.best.push(CleanupPlan {
+                    freed: freed + size,
+                    dirs: chosen.clone(),
+                });
+                if self.best.len() > self.max_plans {
+                    self.best.pop();
+                }
+            } else if slots > 1 {
+                self.search(ix + 1, chosen, freed + size);
+            }
+            chosen.pop();
         }
     }
-    println!("Part 2: {best_dir:?} -> {best_size}");
+}
+
+// Returns up to `max_plans` sets of at most `max_dirs` non-nested directories that free at
+// least the required space, ranked by bytes freed.
+fn plan_cleanup(
+    fs: &FileSystem,
+    disk_size: usize,
+    required_free: usize,
+    max_dirs: usize,
+    max_plans: usize,
+) -> Vec<CleanupPlan> {
+    let dir_sizes = calc_sizes_by_dir(fs);
+    let used = dir_sizes[&fs.root()];
+    let needed = required_free.saturating_sub(disk_size.saturating_sub(used));
+    if needed == 0 {
+        return vec![];
+    }
+
+    let mut candidates: Vec<(NodeId, usize)> = dir_sizes.into_iter().collect();
+    candidates.sort_by_key(|(dir, size)| (std::cmp::Reverse(*size), *dir));
+    let mut search = CleanupSearch {
+        fs,
+        candidates,
+        needed,
+        max_dirs,
+        max_plans,
+        best: BinaryHeap::new(),
+    };
+    search.search(0, &mut vec![], 0);
+    search.best.into_sorted_vec()
+}
+
+fn part2() {
+    let fs = process_input();
+    match plan_cleanup(&fs, 70000000, 30000000, 1, 1).first() {
+        Some(best) => println!("Part 2: {:?} -> {}", fs.path(best.dirs[0]), best.freed),
+        None => println!("Part 2: enough space is already free, nothing needs deleting"),
+    }
+}
+
+fn cleanup_alternatives() {
+    let fs = process_input();
+    println!("Cleanup plans:");
+    for plan in plan_cleanup(&fs, 70000000, 30000000, 3, 5) {
+        let paths = plan.dirs.iter().map(|it| fs.path(*it)).join(", ");
+        println!("  {} bytes: {paths}", plan.freed);
+    }
 }
 
 fn main() {
     part1();
     part2();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|it| it.as_str()).collect_vec()[..] {
+        ["plans"] => cleanup_alternatives(),
+        [mode @ ("tree" | "du"), path] => {
+            let fs = process_input();
+            let id = fs.lookup(path).expect("No such path.");
+            if mode == "tree" {
+                println!("{}", fs.render_tree(id));
+            } else {
+                println!("{}", fs.render_du(id));
+            }
+        }
+        [] => {}
+        _ => panic!("Usage: day07 [plans | tree <path> | du <path>]"),
+    }
 }
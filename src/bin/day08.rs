@@ -1,3 +1,7 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
 use adventofcode2022::read_input_lines;
 
 type Forest = Vec<Vec<u8>>;
@@ -14,75 +18,204 @@ fn parse_forest() -> Forest {
     output
 }
 
-fn count_visible(forest: &Forest) -> u32 {
-    let mut visible_count: u32 = 0;
+struct ForestAnalysis {
+    visible: Vec<Vec<bool>>,
+    scenic_scores: Vec<Vec<u64>>,
+}
 
-    for (row, row_vec) in forest.iter().enumerate() {
-        for (col, height) in row_vec.iter().enumerate() {
-            if row == 0 || forest.iter().take(row).all(|it| it[col] < *height) {
-                visible_count += 1;
-                continue;
-            }
-            if row == forest.len() - 1 || forest.iter().skip(row + 1).all(|it| it[col] < *height) {
-                visible_count += 1;
-                continue;
-            }
-            if col == 0 || row_vec.iter().take(col).all(|it| *it < *height) {
-                visible_count += 1;
-                continue;
-            }
-            if col == row_vec.len() - 1 || row_vec.iter().skip(col + 1).all(|it| *it < *height) {
-                visible_count += 1;
-                continue;
-            }
+// Walks one line of sight keeping a stack of trees that are at least as tall as everything
+// after them, so each tree's nearest blocker is on top once the shorter ones are popped.
+fn sweep_line(
+    forest: &Forest,
+    cells: impl Iterator<Item = (usize, usize)>,
+    analysis: &mut ForestAnalysis,
+) {
+    let mut stack: Vec<(u8, usize)> = vec![];
+    for (pos, (row, col)) in cells.enumerate() {
+        let height = forest[row][col];
+        while stack.last().is_some_and(|(h, _)| *h < height) {
+            stack.pop();
         }
+        let distance = match stack.last() {
+            Some((_, blocker)) => pos - blocker,
+            None => {
+                analysis.visible[row][col] = true;
+                pos
+            }
+        };
+        analysis.scenic_scores[row][col] *= distance as u64;
+        stack.push((height, pos));
     }
-    visible_count
 }
 
-fn best_scenic_score(forest: &Forest) -> u32 {
-    let mut best_score: u32 = 0;
+fn analyze_forest(forest: &Forest) -> ForestAnalysis {
+    let rows = forest.len();
+    let cols = forest.first().map_or(0, |it| it.len());
+    let mut analysis = ForestAnalysis {
+        visible: vec![vec![false; cols]; rows],
+        scenic_scores: vec![vec![1; cols]; rows],
+    };
 
-    for (row, row_vec) in forest.iter().enumerate() {
-        for (col, height) in row_vec.iter().enumerate() {
-            let mut up_score: u32 = 0;
-            for tree in forest.iter().take(row).map(|it| it[col]).rev() {
-                up_score += 1;
-                if tree >= *height {
-                    break;
-                }
-            }
+    for row in 0..rows {
+        sweep_line(forest, (0..cols).map(|col| (row, col)), &mut analysis);
+        sweep_line(forest, (0..cols).rev().map(|col| (row, col)), &mut analysis);
+    }
+    for col in 0..cols {
+        sweep_line(forest, (0..rows).map(|row| (row, col)), &mut analysis);
+        sweep_line(forest, (0..rows).rev().map(|row| (row, col)), &mut analysis);
+    }
+    analysis
+}
 
-            let mut down_score: u32 = 0;
-            for tree in forest.iter().skip(row + 1).map(|it| it[col]) {
-                down_score += 1;
-                if tree >= *height {
-                    break;
-                }
-            }
+fn count_visible(forest: &Forest) -> usize {
+    analyze_forest(forest)
+        .visible
+        .iter()
+        .flatten()
+        .filter(|it| **it)
+        .count()
+}
 
-            let mut left_score: u32 = 0;
-            for tree in row_vec.iter().take(col).rev().copied() {
-                left_score += 1;
-                if tree >= *height {
-                    break;
-                }
-            }
+fn best_scenic_score(forest: &Forest) -> u64 {
+    analyze_forest(forest)
+        .scenic_scores
+        .iter()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or(0)
+}
 
-            let mut right_score: u32 = 0;
-            for tree in row_vec.iter().skip(col + 1).copied() {
-                right_score += 1;
-                if tree >= *height {
-                    break;
-                }
-            }
+type Rgb = [u8; 3];
+
+const HIGHLIGHT: Rgb = [255, 0, 255];
 
-            let total_score = up_score * down_score * left_score * right_score;
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
 
-            best_score = std::cmp::max(best_score, total_score);
+impl Image {
+    fn from_cells<T>(cells: &[Vec<T>], color: impl Fn(&T) -> Rgb) -> Image {
+        Image {
+            width: cells.first().map_or(0, |it| it.len()),
+            height: cells.len(),
+            pixels: cells.iter().flatten().map(color).collect(),
         }
     }
-    best_score
+
+    fn set(&mut self, row: usize, col: usize, color: Rgb) {
+        self.pixels[row * self.width + col] = color;
+    }
+
+    fn write_ppm(&self, path: &Path) -> std::io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.pixels.concat())?;
+        out.flush()
+    }
+
+    fn write_png(&self, path: &Path) -> std::io::Result<()> {
+        let out = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels.concat())?;
+        Ok(())
+    }
+
+    fn to_ansi(&self) -> String {
+        let mut lines = vec![];
+        for row in self.pixels.chunks(self.width.max(1)) {
+            let line: String = row
+                .iter()
+                .map(|[r, g, b]| format!("\x1b[48;2;{r};{g};{b}m  "))
+                .collect();
+            lines.push(format!("{line}\x1b[0m"));
+        }
+        lines.join("\n")
+    }
+}
+
+// Cold-to-hot ramp from dark blue through green to yellow.
+fn heat_color(fraction: f64) -> Rgb {
+    let t = fraction.clamp(0.0, 1.0);
+    let r = (255.0 * (2.0 * t - 1.0).max(0.0)) as u8;
+    let g = (255.0 * (2.0 * t).min(1.0)) as u8;
+    let b = (160.0 * (1.0 - t)) as u8;
+    [r, g, b]
+}
+
+fn best_tree(analysis: &ForestAnalysis) -> Option<(usize, usize)> {
+    analysis
+        .scenic_scores
+        .iter()
+        .enumerate()
+        .flat_map(|(row, it)| it.iter().enumerate().map(move |(col, s)| (*s, row, col)))
+        .max_by_key(|(score, row, col)| (*score, std::cmp::Reverse((*row, *col))))
+        .map(|(_, row, col)| (row, col))
+}
+
+fn forest_images(forest: &Forest, analysis: &ForestAnalysis) -> Vec<(&'static str, Image)> {
+    let max_score = analysis
+        .scenic_scores
+        .iter()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or(0);
+    // Scores span several orders of magnitude, so the heatmap uses a log scale.
+    let log_max = ((max_score + 1) as f64).ln().max(f64::EPSILON);
+    let mut images = vec![
+        (
+            "height",
+            Image::from_cells(forest, |h| heat_color(*h as f64 / 9.0)),
+        ),
+        (
+            "visible",
+            Image::from_cells(&analysis.visible, |v| {
+                if *v {
+                    [255, 255, 255]
+                } else {
+                    [0, 0, 0]
+                }
+            }),
+        ),
+        (
+            "scenic",
+            Image::from_cells(&analysis.scenic_scores, |s| {
+                heat_color(((*s + 1) as f64).ln() / log_max)
+            }),
+        ),
+    ];
+    if let Some((row, col)) = best_tree(analysis) {
+        for (_, image) in images.iter_mut() {
+            image.set(row, col, HIGHLIGHT);
+        }
+    }
+    images
+}
+
+fn export(dir: &Path) {
+    let forest = parse_forest();
+    let analysis = analyze_forest(&forest);
+    std::fs::create_dir_all(dir).unwrap();
+    for (name, image) in forest_images(&forest, &analysis) {
+        image.write_ppm(&dir.join(format!("{name}.ppm"))).unwrap();
+        image.write_png(&dir.join(format!("{name}.png"))).unwrap();
+    }
+}
+
+fn show(name: &str) {
+    let forest = parse_forest();
+    let analysis = analyze_forest(&forest);
+    let (_, image) = forest_images(&forest, &analysis)
+        .into_iter()
+        .find(|(it, _)| *it == name)
+        .expect("Unknown map; expected height, visible or scenic.");
+    println!("{}", image.to_ansi());
 }
 
 fn part1() {
@@ -100,4 +233,12 @@ fn part2() {
 fn main() {
     part1();
     part2();
+
+    let mut args = std::env::args().skip(1);
+    match (args.next().as_deref(), args.next()) {
+        (Some("export"), Some(dir)) => export(Path::new(&dir)),
+        (Some("show"), Some(name)) => show(&name),
+        (Some(mode), _) => panic!("Unknown mode {mode}; expected export <dir> or show <map>."),
+        _ => {}
+    }
 }
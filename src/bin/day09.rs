@@ -1,15 +1,20 @@
 use std::collections::HashSet;
 use std::convert::Infallible;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
 use adventofcode2022::read_input_lines_as;
+use itertools::Itertools;
 
 type Pos = (i32, i32);
 
 #[derive(Debug, Default)]
 struct Rope {
     knots: Vec<Pos>,
-    tail_history: HashSet<Pos>,
+    history: Vec<HashSet<Pos>>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -18,6 +23,26 @@ enum Direction {
     R,
     U,
     D,
+    UL,
+    UR,
+    DL,
+    DR,
+}
+
+impl Direction {
+    fn delta(&self) -> Pos {
+        use Direction::*;
+        match self {
+            L => (-1, 0),
+            R => (1, 0),
+            U => (0, 1),
+            D => (0, -1),
+            UL => (-1, 1),
+            UR => (1, 1),
+            DL => (-1, -1),
+            DR => (1, -1),
+        }
+    }
 }
 
 impl FromStr for Direction {
@@ -29,6 +54,10 @@ impl FromStr for Direction {
             "R" => Self::R,
             "U" => Self::U,
             "D" => Self::D,
+            "UL" => Self::UL,
+            "UR" => Self::UR,
+            "DL" => Self::DL,
+            "DR" => Self::DR,
             _ => panic!("Unknown direction {s}"),
         })
     }
@@ -54,77 +83,231 @@ impl FromStr for Instruction {
 
 impl Rope {
     fn new(size: usize) -> Self {
-        let mut value = Self::default();
-        for _ in 0..size {
-            value.knots.push((0, 0));
+        Rope {
+            knots: vec![(0, 0); size],
+            history: vec![HashSet::from([(0, 0)]); size],
         }
-        value
     }
-    fn apply(&mut self, d: Instruction) {
-        use Direction::*;
-        //println!("{d:?}, start: h{:?} t{:?}", self.head, self.tail);
-        self.tail_history.insert(*self.knots.last().unwrap());
-        if d.num == 0 {
-            return;
+
+    fn tail_history(&self) -> &HashSet<Pos> {
+        self.history.last().unwrap()
+    }
+
+    fn step(&mut self, dir: Direction) {
+        let (dx, dy) = dir.delta();
+        self.knots[0].0 += dx;
+        self.knots[0].1 += dy;
+        for tp in 1..self.knots.len() {
+            let hp = tp - 1;
+            let diff = (
+                self.knots[hp].0 - self.knots[tp].0,
+                self.knots[hp].1 - self.knots[tp].1,
+            );
+            if diff.0.abs() <= 1 && diff.1.abs() <= 1 {
+                break;
+            }
+            self.knots[tp].0 += diff.0.signum();
+            self.knots[tp].1 += diff.1.signum();
         }
-        match d.dir {
-            L => self.knots[0].0 -= 1,
-            R => self.knots[0].0 += 1,
-            U => self.knots[0].1 += 1,
-            D => self.knots[0].1 -= 1,
+        for (knot, history) in self.knots.iter().zip(self.history.iter_mut()) {
+            history.insert(*knot);
         }
-        self.update_tail_pos(1);
-        self.tail_history.insert(*self.knots.last().unwrap());
-        //println!("{d:?}, end: h{:?} t{:?}", self.head, self.tail);
-        self.apply(Instruction {
-            dir: d.dir,
-            num: d.num - 1,
-        });
-    }
-    fn update_tail_pos(&mut self, curr: usize) {
-        if curr >= self.knots.len() {
-            return;
+    }
+
+    fn apply(&mut self, d: Instruction) {
+        for _ in 0..d.num {
+            self.step(d.dir);
         }
-        let hp = curr - 1;
-        let tp = curr;
-        let diff = (
-            self.knots[hp].0 - self.knots[tp].0,
-            self.knots[hp].1 - self.knots[tp].1,
-        );
-        match diff {
-            (n, 0) if n >= 2 => self.knots[tp].0 += 1,
-            (n, 0) if n <= -2 => self.knots[tp].0 -= 1,
-            (0, n) if n >= 2 => self.knots[tp].1 += 1,
-            (0, n) if n <= -2 => self.knots[tp].1 -= 1,
-            (x, y) if x.abs() > 1 || y.abs() > 1 => {
-                self.knots[tp].0 += x / x.abs();
-                self.knots[tp].1 += y / y.abs();
-            }
-            (_, _) => (),
+    }
+
+    fn steps<'a>(
+        &'a mut self,
+        instructions: &'a [Instruction],
+    ) -> impl Iterator<Item = Vec<Pos>> + 'a {
+        instructions
+            .iter()
+            .flat_map(|it| std::iter::repeat_n(it.dir, it.num.max(0) as usize))
+            .map(move |dir| {
+                self.step(dir);
+                self.knots.clone()
+            })
+    }
+}
+
+fn knot_label(ix: usize) -> char {
+    match ix {
+        0 => 'H',
+        1..=9 => char::from_digit(ix as u32, 10).unwrap(),
+        _ => '*',
+    }
+}
+
+// Draws a `width` x `height` window centered on the head, with y increasing upwards.
+fn render_frame(rope: &Rope, width: i32, height: i32) -> String {
+    let (hx, hy) = rope.knots[0];
+    let (min_x, max_y) = (hx - width / 2, hy + height / 2);
+    let mut lines = vec![];
+    for y in ((max_y - height + 1)..=max_y).rev() {
+        let line: String = (min_x..(min_x + width))
+            .map(|x| {
+                if let Some(ix) = rope.knots.iter().position(|it| *it == (x, y)) {
+                    knot_label(ix)
+                } else if (x, y) == (0, 0) {
+                    's'
+                } else if rope.tail_history().contains(&(x, y)) {
+                    '#'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+fn play(size: usize, frame_delay: Duration) {
+    let instructions: Vec<Instruction> = read_input_lines_as(9);
+    let total: i32 = instructions.iter().map(|it| it.num).sum();
+    let mut rope = Rope::new(size);
+    for (ix, ins) in instructions.iter().enumerate() {
+        for step in 0..ins.num {
+            rope.step(ins.dir);
+            print!("\x1b[2J\x1b[H");
+            println!(
+                "Instruction {}/{} {:?} {}/{} ({total} steps total)",
+                ix + 1,
+                instructions.len(),
+                ins.dir,
+                step + 1,
+                ins.num
+            );
+            println!("{}", render_frame(&rope, 80, 30));
+            std::thread::sleep(frame_delay);
         }
-        self.update_tail_pos(curr + 1);
     }
 }
 
-fn part1() {
-    let mut rope = Rope::new(2);
+fn trail_bounds(rope: &Rope) -> (Pos, Pos) {
+    let cells = rope.tail_history().iter().chain(rope.knots.iter());
+    let (xs, ys): (Vec<i32>, Vec<i32>) = cells.copied().unzip();
+    (
+        (*xs.iter().min().unwrap(), *ys.iter().min().unwrap()),
+        (*xs.iter().max().unwrap(), *ys.iter().max().unwrap()),
+    )
+}
+
+fn write_svg(rope: &Rope, path: &Path) -> std::io::Result<()> {
+    let ((min_x, min_y), (max_x, max_y)) = trail_bounds(rope);
+    let (width, height) = (max_x - min_x + 1, max_y - min_y + 1);
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {width} {height}" width="{}" height="{}">"#,
+        width * 4,
+        height * 4
+    )?;
+    writeln!(out, r#"<rect width="100%" height="100%" fill="black"/>"#)?;
+    for (x, y) in rope.tail_history().iter().sorted() {
+        writeln!(
+            out,
+            r#"<rect x="{}" y="{}" width="1" height="1" fill="lime"/>"#,
+            x - min_x,
+            max_y - y
+        )?;
+    }
+    for (ix, (x, y)) in rope.knots.iter().enumerate().rev() {
+        let fill = if ix == 0 { "red" } else { "white" };
+        writeln!(
+            out,
+            r#"<circle cx="{}.5" cy="{}.5" r="0.5" fill="{fill}"/>"#,
+            x - min_x,
+            max_y - y
+        )?;
+    }
+    writeln!(out, "</svg>")?;
+    out.flush()
+}
+
+fn write_ppm(rope: &Rope, path: &Path) -> std::io::Result<()> {
+    let ((min_x, min_y), (max_x, max_y)) = trail_bounds(rope);
+    let (width, height) = ((max_x - min_x + 1) as usize, (max_y - min_y + 1) as usize);
+    let mut pixels = vec![0u8; width * height * 3];
+    let mut paint = |(x, y): Pos, color: [u8; 3]| {
+        let ix = ((max_y - y) as usize * width + (x - min_x) as usize) * 3;
+        pixels[ix..ix + 3].copy_from_slice(&color);
+    };
+    for pos in rope.tail_history() {
+        paint(*pos, [0, 255, 0]);
+    }
+    for (ix, pos) in rope.knots.iter().enumerate().rev() {
+        paint(
+            *pos,
+            if ix == 0 {
+                [255, 0, 0]
+            } else {
+                [255, 255, 255]
+            },
+        );
+    }
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "P6\n{width} {height}\n255\n")?;
+    out.write_all(&pixels)?;
+    out.flush()
+}
+
+fn simulate(size: usize) -> Rope {
+    let mut rope = Rope::new(size);
     let instructions: Vec<Instruction> = read_input_lines_as(9);
     for ins in instructions {
         rope.apply(ins);
     }
-    println!("Part 1: {}", rope.tail_history.len());
+    rope
+}
+
+fn part1() {
+    let rope = simulate(2);
+    println!("Part 1: {}", rope.tail_history().len());
 }
 
 fn part2() {
-    let mut rope = Rope::new(10);
+    let rope = simulate(10);
+    println!("Part 2: {}", rope.tail_history().len());
+}
+
+fn knot_report() {
     let instructions: Vec<Instruction> = read_input_lines_as(9);
-    for ins in instructions {
-        rope.apply(ins);
-    }
-    println!("Part 1: {}", rope.tail_history.len());
+    let mut rope = Rope::new(10);
+    let farthest = rope
+        .steps(&instructions)
+        .map(|knots| knots[0].0.abs() + knots[0].1.abs())
+        .max()
+        .unwrap_or(0);
+    let visited: Vec<usize> = rope.history.iter().map(|it| it.len()).collect();
+    println!("Visited per knot: {visited:?} (head reached distance {farthest})");
 }
 
 fn main() {
     part1();
     part2();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|it| it.as_str()).collect::<Vec<_>>()[..] {
+        ["knots"] => knot_report(),
+        ["play"] => play(10, Duration::from_millis(30)),
+        ["play", delay] => play(10, Duration::from_millis(delay.parse().unwrap())),
+        ["play", delay, size] => play(
+            size.parse().unwrap(),
+            Duration::from_millis(delay.parse().unwrap()),
+        ),
+        ["export", dir] => {
+            let rope = simulate(10);
+            let dir = Path::new(dir);
+            std::fs::create_dir_all(dir).unwrap();
+            write_svg(&rope, &dir.join("rope.svg")).unwrap();
+            write_ppm(&rope, &dir.join("rope.ppm")).unwrap();
+        }
+        [] => {}
+        _ => panic!("Usage: day09 [knots | play [delay_ms [knots]] | export <dir>]"),
+    }
 }
@@ -1,4 +1,6 @@
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
+use std::fmt::Display;
 use std::str::FromStr;
 
 use adventofcode2022::read_input_lines_as;
@@ -10,6 +12,15 @@ enum Instruction {
     Add(i32),
 }
 
+impl Instruction {
+    fn cycles(&self) -> u32 {
+        match self {
+            Instruction::Noop => 1,
+            Instruction::Add(_) => 2,
+        }
+    }
+}
+
 impl FromStr for Instruction {
     type Err = Infallible;
 
@@ -24,18 +35,21 @@ impl FromStr for Instruction {
     }
 }
 
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Add(n) => write!(f, "addx {n}"),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 struct State {
     cycle: u32,
     register: i32,
 }
 
-#[derive(Clone, Copy, Debug)]
-enum EvalResult {
-    Ongoing,
-    Finished,
-}
-
 impl State {
     fn initial() -> State {
         State {
@@ -43,60 +57,497 @@ impl State {
             register: 1,
         }
     }
-    fn signal_strength(&self) -> i32 {
-        self.register * self.cycle as i32
-    }
+
     fn apply(self, ins: Instruction) -> State {
         use Instruction::*;
+        let cycle = self.cycle + ins.cycles();
         match ins {
-            Noop => State {
-                cycle: self.cycle + 1,
-                ..self
-            },
+            Noop => State { cycle, ..self },
             Add(n) => State {
-                cycle: self.cycle + 2,
+                cycle,
                 register: self.register + n,
             },
         }
     }
+}
+
+// The register value during a single cycle.
+#[derive(Clone, Copy, Debug)]
+struct Tick {
+    cycle: u32,
+    register: i32,
+}
+
+impl Tick {
+    fn signal_strength(&self) -> i32 {
+        self.register * self.cycle as i32
+    }
+}
+
+struct Cpu<I> {
+    program: I,
+    state: State,
+    cycle: u32,
+    current: Option<(Instruction, u32)>,
+}
 
-    fn state_during_cycle(&self, ins: &[Instruction], cycle: u32) -> (State, EvalResult) {
-        let mut state = *self;
-        let mut eval_result = EvalResult::Finished;
+impl<I: Iterator<Item = Instruction>> Cpu<I> {
+    fn new(program: I) -> Self {
+        Cpu {
+            program,
+            state: State::initial(),
+            cycle: State::initial().cycle,
+            current: None,
+        }
+    }
 
-        for i in ins {
-            if state.cycle >= std::cmp::max(cycle, 1) - 1 {
-                eval_result = EvalResult::Ongoing;
-                break;
+    fn run(self, hooks: &mut [&mut dyn CycleHook]) -> State {
+        let mut cpu = self;
+        for tick in cpu.by_ref() {
+            for hook in hooks.iter_mut() {
+                hook.on_tick(tick);
             }
-            state = state.apply(*i);
         }
+        cpu.state
+    }
+}
+
+impl<I: Iterator<Item = Instruction>> Iterator for Cpu<I> {
+    type Item = Tick;
+
+    fn next(&mut self) -> Option<Tick> {
+        let (ins, elapsed) = match self.current.take() {
+            Some(current) => current,
+            None => (self.program.next()?, 0),
+        };
+        let tick = Tick {
+            cycle: self.cycle,
+            register: self.state.register,
+        };
+        if elapsed + 1 >= ins.cycles() {
+            self.state = self.state.apply(ins);
+        } else {
+            self.current = Some((ins, elapsed + 1));
+        }
+        self.cycle += 1;
+        Some(tick)
+    }
+}
 
-        state.cycle = cycle;
-        (state, eval_result)
+trait CycleHook {
+    fn on_tick(&mut self, tick: Tick);
+}
+
+struct SignalSampler {
+    cycles: Vec<u32>,
+    total: i32,
+}
+
+impl CycleHook for SignalSampler {
+    fn on_tick(&mut self, tick: Tick) {
+        if self.cycles.contains(&tick.cycle) {
+            self.total += tick.signal_strength();
+        }
     }
+}
+
+#[derive(Default)]
+struct Crt {
+    pixels: Vec<bool>,
+}
 
-    fn draw_result(&self) -> bool {
-        let cyc_pos = (self.cycle as i32 - 1) % 40;
-        ((cyc_pos - 1)..=(cyc_pos + 1)).contains(&self.register)
+impl CycleHook for Crt {
+    fn on_tick(&mut self, tick: Tick) {
+        let cyc_pos = (tick.cycle as i32 - 1) % 40;
+        self.pixels
+            .push(((cyc_pos - 1)..=(cyc_pos + 1)).contains(&tick.register));
     }
 }
 
 fn part1() {
     let instructions = read_input_lines_as::<Instruction>(10);
-    let state = State::initial();
-    let result: i32 = [
-        state.state_during_cycle(&instructions, 20),
-        state.state_during_cycle(&instructions, 60),
-        state.state_during_cycle(&instructions, 100),
-        state.state_during_cycle(&instructions, 140),
-        state.state_during_cycle(&instructions, 180),
-        state.state_during_cycle(&instructions, 220),
-    ]
-    .iter()
-    .map(|it| it.0.signal_strength())
-    .sum();
-    println!("Part 1: {result}");
+    let mut sampler = SignalSampler {
+        cycles: vec![20, 60, 100, 140, 180, 220],
+        total: 0,
+    };
+    Cpu::new(instructions.into_iter()).run(&mut [&mut sampler]);
+    println!("Part 1: {}", sampler.total);
+}
+
+// Glyphs of the standard 4x6 puzzle font, one row per string.
+const FONT: &[(char, [&str; 6])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+const SCREEN_WIDTH: usize = 40;
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+
+#[derive(Debug)]
+struct UnknownGlyph {
+    index: usize,
+    bitmap: Vec<String>,
+}
+
+impl Display for UnknownGlyph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Unknown glyph at position {}:", self.index)?;
+        write!(f, "{}", self.bitmap.join("\n"))
+    }
+}
+
+// Each glyph sits in a 5-column cell whose last column is spacing.
+fn ocr(draw_results: &[bool]) -> Result<String, Vec<UnknownGlyph>> {
+    let rows = draw_results
+        .chunks(SCREEN_WIDTH)
+        .take(GLYPH_HEIGHT)
+        .collect_vec();
+    let mut text = String::new();
+    let mut unknown = vec![];
+    for index in 0..(SCREEN_WIDTH / (GLYPH_WIDTH + 1)) {
+        let col = index * (GLYPH_WIDTH + 1);
+        let bitmap = rows
+            .iter()
+            .map(|row| {
+                (col..col + GLYPH_WIDTH)
+                    .map(|c| if row.get(c) == Some(&true) { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect_vec();
+        match FONT.iter().find(|(_, glyph)| glyph[..] == bitmap[..]) {
+            Some((c, _)) => text.push(*c),
+            None if bitmap.iter().all(|it| !it.contains('#')) => text.push(' '),
+            None => unknown.push(UnknownGlyph { index, bitmap }),
+        }
+    }
+    if unknown.is_empty() {
+        Ok(text.trim_end().to_string())
+    } else {
+        Err(unknown)
+    }
+}
+
+#[derive(Debug)]
+struct AsmError {
+    line: usize,
+    message: String,
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn asm_error(line: usize, message: impl Into<String>) -> AsmError {
+    AsmError {
+        line,
+        message: message.into(),
+    }
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<(usize, String)>,
+}
+
+const MAX_MACRO_DEPTH: usize = 32;
+
+fn expand_macros(
+    statements: &[(usize, String)],
+    macros: &HashMap<String, Macro>,
+    depth: usize,
+    output: &mut Vec<(usize, String)>,
+) -> Result<(), AsmError> {
+    for (line, statement) in statements {
+        let words = statement.split_ascii_whitespace().collect_vec();
+        let Some(mac) = macros.get(words[0]) else {
+            output.push((*line, statement.clone()));
+            continue;
+        };
+        if depth >= MAX_MACRO_DEPTH {
+            return Err(asm_error(
+                *line,
+                format!("macro {} nests too deeply", words[0]),
+            ));
+        }
+        let args = &words[1..];
+        if args.len() != mac.params.len() {
+            return Err(asm_error(
+                *line,
+                format!(
+                    "macro {} takes {} arguments, got {}",
+                    words[0],
+                    mac.params.len(),
+                    args.len()
+                ),
+            ));
+        }
+        let body = mac
+            .body
+            .iter()
+            .map(|(_, text)| {
+                let text = mac
+                    .params
+                    .iter()
+                    .zip(args)
+                    .fold(text.clone(), |acc, (param, arg)| {
+                        acc.replace(&format!("${param}"), arg)
+                    });
+                (*line, text)
+            })
+            .collect_vec();
+        expand_macros(&body, macros, depth + 1, output)?;
+    }
+    Ok(())
+}
+
+fn parse_operand(
+    line: usize,
+    token: Option<&str>,
+    labels: &HashMap<String, i32>,
+) -> Result<i32, AsmError> {
+    let token = token.ok_or_else(|| asm_error(line, "missing operand"))?;
+    if let Ok(n) = token.parse() {
+        return Ok(n);
+    }
+    let (sign, name) = match token.strip_prefix('-') {
+        Some(name) => (-1, name),
+        None => (1, token),
+    };
+    labels
+        .get(name)
+        .map(|it| sign * it)
+        .ok_or_else(|| asm_error(line, format!("unknown operand {token}")))
+}
+
+fn statement_cycles(
+    line: usize,
+    words: &[&str],
+    labels: &HashMap<String, i32>,
+) -> Result<i32, AsmError> {
+    match words[0] {
+        "noop" => Ok(1),
+        "addx" | "setx" => Ok(2),
+        "wait" => match parse_operand(line, words.get(1).copied(), labels)? {
+            n if n < 0 => Err(asm_error(line, format!("cannot wait {n} cycles"))),
+            n => Ok(n),
+        },
+        other => Err(asm_error(line, format!("unknown instruction {other}"))),
+    }
+}
+
+// Assembles a listing of `noop`/`addx` plus a few conveniences:
+//   `; comment`, `name:` labels holding the cycle at that point (usable as operands),
+//   `wait n` for n noops, `setx n` to load an absolute register value, and
+//   `.macro name params...` / `.endm` blocks whose bodies refer to params as `$param`.
+// Since a label's value depends on the length of everything before it, `wait` can only use
+// labels defined earlier, and macro bodies can't define labels at all.
+fn assemble(source: &str) -> Result<Vec<Instruction>, AsmError> {
+    let mut macros = HashMap::new();
+    let mut statements = vec![];
+    let mut defining: Option<(usize, String, Macro)> = None;
+    for (ix, raw) in source.lines().enumerate() {
+        let line = ix + 1;
+        let text = raw.split([';', '#']).next().unwrap().trim();
+        if text.is_empty() {
+            continue;
+        }
+        let words = text.split_ascii_whitespace().collect_vec();
+        match (words[0], defining.as_mut()) {
+            (".macro", Some(_)) => return Err(asm_error(line, "nested macro definition")),
+            (".macro", None) => {
+                let name = words
+                    .get(1)
+                    .ok_or_else(|| asm_error(line, "macro needs a name"))?;
+                let params = words[2..].iter().map(|it| it.to_string()).collect();
+                defining = Some((
+                    line,
+                    name.to_string(),
+                    Macro {
+                        params,
+                        body: vec![],
+                    },
+                ));
+            }
+            (".endm", Some(_)) => {
+                let (_, name, mac) = defining.take().unwrap();
+                macros.insert(name, mac);
+            }
+            (".endm", None) => return Err(asm_error(line, ".endm without .macro")),
+            (_, Some(_)) if text.contains(':') => {
+                return Err(asm_error(line, "labels aren't allowed inside macros"))
+            }
+            (_, Some((_, _, mac))) => mac.body.push((line, text.to_string())),
+            (_, None) => match text.split_once(':') {
+                Some((label, rest)) => {
+                    statements.push((line, format!("{}:", label.trim())));
+                    if !rest.trim().is_empty() {
+                        statements.push((line, rest.trim().to_string()));
+                    }
+                }
+                None => statements.push((line, text.to_string())),
+            },
+        }
+    }
+    if let Some((line, name, _)) = defining {
+        return Err(asm_error(line, format!("macro {name} is never closed")));
+    }
+
+    let mut expanded = vec![];
+    expand_macros(&statements, &macros, 0, &mut expanded)?;
+
+    let defined: HashSet<&str> = expanded
+        .iter()
+        .filter_map(|(_, statement)| statement.strip_suffix(':'))
+        .collect();
+    let mut labels = HashMap::new();
+    let mut cycle = State::initial().cycle as i32;
+    for (line, statement) in &expanded {
+        if let Some(label) = statement.strip_suffix(':') {
+            if labels.insert(label.to_string(), cycle).is_some() {
+                return Err(asm_error(*line, format!("duplicate label {label}")));
+            }
+            continue;
+        }
+        let words = statement.split_ascii_whitespace().collect_vec();
+        cycle += statement_cycles(*line, &words, &labels).map_err(|err| match words[..] {
+            ["wait", operand] if defined.contains(operand.trim_start_matches('-')) => asm_error(
+                *line,
+                format!("wait can't use {operand}, which is defined after it"),
+            ),
+            _ => err,
+        })?;
+    }
+
+    let mut program = vec![];
+    let mut state = State::initial();
+    for (line, statement) in &expanded {
+        if statement.ends_with(':') {
+            continue;
+        }
+        let words = statement.split_ascii_whitespace().collect_vec();
+        let operand = || parse_operand(*line, words.get(1).copied(), &labels);
+        let emitted = match words[0] {
+            "noop" => vec![Instruction::Noop],
+            "addx" => vec![Instruction::Add(operand()?)],
+            "setx" => vec![Instruction::Add(operand()? - state.register)],
+            "wait" => vec![Instruction::Noop; operand()? as usize],
+            other => return Err(asm_error(*line, format!("unknown instruction {other}"))),
+        };
+        for ins in emitted {
+            state = state.apply(ins);
+            program.push(ins);
+        }
+    }
+    Ok(program)
+}
+
+const SCREEN_HEIGHT: usize = 6;
+
+// Finds the shortest program that draws `image` in exactly one frame. Any register value
+// outside -2..=41 lights the same pixels as one of those, so that range is all we search.
+fn synthesize(image: &[bool]) -> Option<Vec<Instruction>> {
+    let cycles = SCREEN_WIDTH * SCREEN_HEIGHT;
+    if image.len() != cycles {
+        return None;
+    }
+    let values = -2..=(SCREEN_WIDTH as i32 + 1);
+    let slot = |x: i32| (x - values.start()) as usize;
+    let fits = |cycle: usize, x: i32| {
+        let pos = (cycle % SCREEN_WIDTH) as i32;
+        ((x - 1)..=(x + 1)).contains(&pos) == image[cycle]
+    };
+
+    // best[cycle][x] is the fewest instructions reaching `cycle` with register `x`, plus how.
+    let mut best: Vec<Vec<Option<(usize, usize, Instruction)>>> =
+        vec![vec![None; values.clone().count()]; cycles + 1];
+    let start = State::initial().register;
+    best[0][slot(start)] = Some((0, 0, Instruction::Noop));
+    for cycle in 0..cycles {
+        for x in values.clone() {
+            let Some((count, _, _)) = best[cycle][slot(x)] else {
+                continue;
+            };
+            let mut offer = |next: usize, y: i32, ins: Instruction| {
+                let entry = &mut best[next][slot(y)];
+                if entry.is_none_or(|(c, _, _)| count + 1 < c) {
+                    *entry = Some((count + 1, slot(x), ins));
+                }
+            };
+            if !fits(cycle, x) {
+                continue;
+            }
+            offer(cycle + 1, x, Instruction::Noop);
+            if cycle + 2 <= cycles && fits(cycle + 1, x) {
+                for y in values.clone() {
+                    offer(cycle + 2, y, Instruction::Add(y - x));
+                }
+            }
+        }
+    }
+
+    let (mut x_slot, _) = best[cycles]
+        .iter()
+        .enumerate()
+        .filter_map(|(ix, it)| it.map(|(count, _, _)| (ix, count)))
+        .min_by_key(|(_, count)| *count)?;
+    let mut cycle = cycles;
+    let mut program = vec![];
+    while cycle > 0 {
+        let (_, prev, ins) = best[cycle][x_slot].unwrap();
+        program.push(ins);
+        cycle -= ins.cycles() as usize;
+        x_slot = prev;
+    }
+    program.reverse();
+    Some(program)
+}
+
+fn text_image(text: &str) -> Option<Vec<bool>> {
+    let glyphs: Vec<&[&str; 6]> = text
+        .chars()
+        .map(|c| FONT.iter().find(|(it, _)| *it == c).map(|(_, glyph)| glyph))
+        .collect::<Option<_>>()?;
+    let mut image = vec![false; SCREEN_WIDTH * SCREEN_HEIGHT];
+    for (index, glyph) in glyphs.iter().enumerate() {
+        for (row, bits) in glyph.iter().enumerate() {
+            for (col, bit) in bits.chars().enumerate() {
+                let x = index * (GLYPH_WIDTH + 1) + col;
+                if x < SCREEN_WIDTH {
+                    image[row * SCREEN_WIDTH + x] = bit == '#';
+                }
+            }
+        }
+    }
+    Some(image)
+}
+
+fn draw_program(program: &[Instruction]) -> Vec<bool> {
+    let final_state = program
+        .iter()
+        .fold(State::initial(), |state, ins| state.apply(*ins));
+    let mut crt = Crt::default();
+    let cpu_state = Cpu::new(program.iter().copied()).run(&mut [&mut crt]);
+    assert_eq!(final_state.cycle, cpu_state.cycle);
+    assert_eq!(final_state.register, cpu_state.register);
+    crt.pixels
 }
 
 fn render(draw_results: &[bool]) {
@@ -110,21 +561,62 @@ fn render(draw_results: &[bool]) {
 
 fn part2() {
     let instructions = read_input_lines_as::<Instruction>(10);
-    let mut draw_results = vec![];
-    let mut i: u32 = 1;
-    loop {
-        let (s, res) = State::initial().state_during_cycle(&instructions, i);
-        draw_results.push(s.draw_result());
-        if matches!(res, EvalResult::Finished) {
-            break;
+    let mut crt = Crt::default();
+    Cpu::new(instructions.into_iter()).run(&mut [&mut crt]);
+    match ocr(&crt.pixels) {
+        Ok(text) => println!("Part 2: {text}"),
+        Err(unknown) => {
+            println!("Part 2:");
+            render(&crt.pixels);
+            for glyph in unknown {
+                println!("{glyph}");
+            }
         }
-        i += 1;
     }
-    println!("Part 2:");
-    render(&draw_results);
+}
+
+fn synthesize_text(text: &str) {
+    let Some(image) = text_image(text) else {
+        println!("Drawing failed: {text:?} uses a letter missing from the font");
+        return;
+    };
+    // The register starts at 1, so some images can't be drawn from the first cycle on.
+    let Some(program) = synthesize(&image) else {
+        println!("Drawing failed: no program draws {text:?}");
+        return;
+    };
+    for ins in &program {
+        println!("{ins}");
+    }
+    let drawn = draw_program(&program);
+    assert!(drawn == image, "Synthesized program draws the wrong image.");
+    println!(
+        "Synthesized {} instructions drawing {:?}",
+        program.len(),
+        ocr(&drawn).unwrap()
+    );
 }
 
 fn main() {
     part1();
     part2();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|it| it.as_str()).collect_vec()[..] {
+        ["asm", path] => {
+            let source = std::fs::read_to_string(path).unwrap();
+            match assemble(&source) {
+                Ok(program) => {
+                    for ins in &program {
+                        println!("{ins}");
+                    }
+                    render(&draw_program(&program));
+                }
+                Err(err) => println!("Assembly failed: {err}"),
+            }
+        }
+        ["draw", text] => synthesize_text(text),
+        [] => {}
+        _ => panic!("Usage: day10 [asm <file> | draw <TEXT>]"),
+    }
 }
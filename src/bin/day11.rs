@@ -1,43 +1,191 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::fmt::Display;
+use std::path::Path;
 use std::str::FromStr;
 
 use adventofcode2022::read_blank_line_delimited_blocks_as;
 use itertools::Itertools;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{char, digit1, newline, space1};
-use nom::combinator::opt;
-use nom::multi::separated_list1;
-use nom::sequence::tuple;
+use nom::character::complete::{char, digit1, newline, one_of, space0, space1};
+use nom::combinator::{map, opt};
+use nom::multi::{many0, separated_list1};
+use nom::sequence::{delimited, preceded, tuple};
 use nom::IResult;
+use num_bigint::BigUint;
+use serde_json::{json, Value};
 
 #[derive(Clone, Copy, Debug)]
-enum Arg {
-    Old,
-    Lit(u64),
+enum Op {
+    Plus,
+    Minus,
+    Times,
+    Divide,
+    Modulo,
+    Power,
 }
 
-impl Arg {
-    fn eval(self, curr_worry: u64) -> u64 {
+impl Op {
+    fn symbol(self) -> char {
         match self {
-            Arg::Old => curr_worry,
-            Arg::Lit(n) => n,
+            Self::Plus => '+',
+            Self::Minus => '-',
+            Self::Times => '*',
+            Self::Divide => '/',
+            Self::Modulo => '%',
+            Self::Power => '^',
         }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
-enum Op {
-    Plus,
-    Times,
+#[derive(Debug)]
+struct WorryError {
+    op: Op,
+    lhs: String,
+    rhs: String,
 }
 
-impl Op {
-    fn eval(self, lhs: u64, rhs: u64) -> u64 {
+impl Display for WorryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} is not a representable worry level",
+            self.lhs,
+            self.op.symbol(),
+            self.rhs
+        )
+    }
+}
+
+#[derive(Debug)]
+enum ModularError {
+    Unsupported { monkey: usize, op: Op },
+    Worry(WorryError),
+}
+
+impl From<WorryError> for ModularError {
+    fn from(err: WorryError) -> Self {
+        ModularError::Worry(err)
+    }
+}
+
+impl Display for ModularError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Plus => lhs + rhs,
-            Self::Times => lhs * rhs,
+            ModularError::Unsupported { monkey, op } => write!(
+                f,
+                "monkey {monkey} uses '{}', which doesn't preserve worry levels modulo the test divisors",
+                op.symbol()
+            ),
+            ModularError::Worry(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+trait Worry: Clone + Display {
+    fn from_u64(n: u64) -> Self;
+    fn to_json(&self) -> Value;
+    fn combine(op: Op, lhs: Self, rhs: Self) -> Option<Self>;
+    fn is_multiple_of(&self, divisor: u64) -> bool;
+}
+
+impl Worry for u64 {
+    fn from_u64(n: u64) -> Self {
+        n
+    }
+
+    fn to_json(&self) -> Value {
+        json!(self)
+    }
+
+    fn combine(op: Op, lhs: Self, rhs: Self) -> Option<Self> {
+        match op {
+            Op::Plus => lhs.checked_add(rhs),
+            Op::Minus => lhs.checked_sub(rhs),
+            Op::Times => lhs.checked_mul(rhs),
+            Op::Divide => lhs.checked_div(rhs),
+            Op::Modulo => lhs.checked_rem(rhs),
+            Op::Power => lhs.checked_pow(u32::try_from(rhs).ok()?),
+        }
+    }
+
+    fn is_multiple_of(&self, divisor: u64) -> bool {
+        u64::is_multiple_of(*self, divisor)
+    }
+}
+
+impl Worry for BigUint {
+    fn from_u64(n: u64) -> Self {
+        BigUint::from(n)
+    }
+
+    // Values too big for a JSON number are written as decimal strings.
+    fn to_json(&self) -> Value {
+        match u64::try_from(self) {
+            Ok(n) => json!(n),
+            Err(_) => json!(self.to_string()),
+        }
+    }
+
+    fn combine(op: Op, lhs: Self, rhs: Self) -> Option<Self> {
+        match op {
+            Op::Plus => Some(lhs + rhs),
+            Op::Minus => (lhs >= rhs).then(|| lhs - rhs),
+            Op::Times => Some(lhs * rhs),
+            Op::Divide => (rhs != BigUint::ZERO).then(|| lhs / rhs),
+            Op::Modulo => (rhs != BigUint::ZERO).then(|| lhs % rhs),
+            Op::Power => Some(lhs.pow(u32::try_from(&rhs).ok()?)),
+        }
+    }
+
+    fn is_multiple_of(&self, divisor: u64) -> bool {
+        self % divisor == BigUint::ZERO
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Old,
+    Lit(u64),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn uses_old(&self) -> bool {
+        match self {
+            Expr::Old => true,
+            Expr::Lit(_) => false,
+            Expr::Binary(_, lhs, rhs) => lhs.uses_old() || rhs.uses_old(),
+        }
+    }
+
+    // Finds an operator whose result changes if `old` is replaced by something congruent to it,
+    // which rules out reducing worry levels modulo the test divisors. Constant subexpressions
+    // are evaluated exactly, so anything goes inside them.
+    fn non_modular_op(&self) -> Option<Op> {
+        match self {
+            Expr::Binary(op, lhs, rhs) if self.uses_old() => match op {
+                Op::Plus | Op::Times => lhs.non_modular_op().or_else(|| rhs.non_modular_op()),
+                Op::Power if !rhs.uses_old() => lhs.non_modular_op(),
+                _ => Some(*op),
+            },
+            _ => None,
+        }
+    }
+
+    fn eval<W: Worry>(&self, curr_worry: &W) -> Result<W, WorryError> {
+        match self {
+            Expr::Old => Ok(curr_worry.clone()),
+            Expr::Lit(n) => Ok(W::from_u64(*n)),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(curr_worry)?, rhs.eval(curr_worry)?);
+                W::combine(*op, lhs.clone(), rhs.clone()).ok_or_else(|| WorryError {
+                    op: *op,
+                    lhs: lhs.to_string(),
+                    rhs: rhs.to_string(),
+                })
+            }
         }
     }
 }
@@ -46,7 +194,7 @@ impl Op {
 struct Monkey {
     index: usize,
     starting_items: Vec<u64>,
-    transform: (Op, Arg, Arg),
+    transform: Expr,
     test_divisor: u64,
     true_target_index: usize,
     false_target_index: usize,
@@ -73,46 +221,80 @@ fn parse_items(s: &str) -> IResult<&str, Vec<u64>> {
     Ok((input, parsed_items))
 }
 
-fn parse_operand(s: &str) -> IResult<&str, Arg> {
-    let (input, arg) = alt((tag("old"), digit1))(s)?;
+fn parse_atom(s: &str) -> IResult<&str, Expr> {
+    let (input, _) = space0(s)?;
+    let (input, atom) = alt((
+        map(tag("old"), |_| Expr::Old),
+        map(digit1, |it: &str| Expr::Lit(it.parse().unwrap())),
+        delimited(char('('), parse_expr, tuple((space0, char(')')))),
+    ))(input)?;
+    Ok((input, atom))
+}
+
+fn parse_operator<'a>(ops: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, Op> {
+    move |s| {
+        let (input, (_, op)) = tuple((space0, one_of(ops)))(s)?;
+        Ok((
+            input,
+            match op {
+                '+' => Op::Plus,
+                '-' => Op::Minus,
+                '*' => Op::Times,
+                '/' => Op::Divide,
+                '%' => Op::Modulo,
+                '^' => Op::Power,
+                other => panic!("Unknown op {other}"),
+            },
+        ))
+    }
+}
 
+// Exponentiation binds tightest and associates to the right.
+fn parse_power(s: &str) -> IResult<&str, Expr> {
+    let (input, base) = parse_atom(s)?;
+    let (input, exponent) = opt(preceded(parse_operator("^"), parse_power))(input)?;
     Ok((
         input,
-        match arg {
-            "old" => Arg::Old,
-            a => Arg::Lit(a.parse().unwrap()),
+        match exponent {
+            Some(exponent) => Expr::Binary(Op::Power, Box::new(base), Box::new(exponent)),
+            None => base,
         },
     ))
 }
 
-fn parse_operator(s: &str) -> IResult<&str, Op> {
-    let (input, op) = alt((char('+'), char('*')))(s)?;
+fn parse_left_assoc<'a>(
+    ops: &'static str,
+    operand: fn(&'a str) -> IResult<&'a str, Expr>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Expr> {
+    move |s| {
+        let (input, first) = operand(s)?;
+        let (input, rest) = many0(tuple((parse_operator(ops), operand)))(input)?;
+        let expr = rest.into_iter().fold(first, |lhs, (op, rhs)| {
+            Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+        });
+        Ok((input, expr))
+    }
+}
 
-    Ok((
-        input,
-        match op {
-            '+' => Op::Plus,
-            '*' => Op::Times,
-            other => panic!("Unknown op {other}"),
-        },
-    ))
+fn parse_term(s: &str) -> IResult<&str, Expr> {
+    parse_left_assoc("*/%", parse_power)(s)
 }
 
-fn parse_transform(s: &str) -> IResult<&str, (Op, Arg, Arg)> {
-    let (input, (_, _, _, _, _, lhs, _, op, _, rhs, _)) = tuple((
+fn parse_expr(s: &str) -> IResult<&str, Expr> {
+    parse_left_assoc("+-", parse_term)(s)
+}
+
+fn parse_transform(s: &str) -> IResult<&str, Expr> {
+    let (input, (_, _, _, _, expr, _, _)) = tuple((
         space1,
         tag("Operation:"),
         space1,
         tag("new ="),
-        space1,
-        parse_operand,
-        space1,
-        parse_operator,
-        space1,
-        parse_operand,
+        parse_expr,
+        space0,
         newline,
     ))(s)?;
-    Ok((input, (op, lhs, rhs)))
+    Ok((input, expr))
 }
 
 fn parse_test_divisor(s: &str) -> IResult<&str, u64> {
@@ -171,67 +353,466 @@ impl FromStr for Monkey {
     }
 }
 
-fn eval_worry_transform(curr: u64, transform: (Op, Arg, Arg)) -> u64 {
-    let (op, lhs, rhs) = transform;
-    op.eval(lhs.eval(curr), rhs.eval(curr))
-}
-
 fn update_inspection_count(sum: &mut HashMap<usize, usize>, additional: HashMap<usize, usize>) {
     for (monkey, count) in additional {
         sum.insert(monkey, count + sum.get(&monkey).copied().unwrap_or(0));
     }
 }
 
-fn run_one_round(
-    monkeys: &mut [Monkey],
-    worry_management_fn: impl Fn(u64) -> u64,
-) -> HashMap<usize, usize> {
+#[derive(Clone, Debug)]
+struct Item<W> {
+    id: usize,
+    worry: W,
+}
+
+fn initial_queues<W: Worry>(monkeys: &[Monkey]) -> Vec<Vec<Item<W>>> {
+    let mut next_id = 0..;
+    monkeys
+        .iter()
+        .map(|it| {
+            it.starting_items
+                .iter()
+                .map(|n| Item {
+                    id: next_id.next().unwrap(),
+                    worry: W::from_u64(*n),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+struct Hop {
+    round: usize,
+    monkey: usize,
+    worry: Value,
+    target: usize,
+}
+
+struct ItemTrace {
+    start_monkey: usize,
+    start_worry: Value,
+    hops: Vec<Hop>,
+}
+
+struct Tracer {
+    snapshot_rounds: Vec<usize>,
+    items: Vec<ItemTrace>,
+    snapshots: Vec<(usize, Value)>,
+}
+
+impl Tracer {
+    fn new<W: Worry>(queues: &[Vec<Item<W>>], snapshot_rounds: Vec<usize>) -> Tracer {
+        let mut items = vec![];
+        for (monkey, queue) in queues.iter().enumerate() {
+            for item in queue {
+                assert!(item.id == items.len());
+                items.push(ItemTrace {
+                    start_monkey: monkey,
+                    start_worry: item.worry.to_json(),
+                    hops: vec![],
+                });
+            }
+        }
+        let mut tracer = Tracer {
+            snapshot_rounds,
+            items,
+            snapshots: vec![],
+        };
+        tracer.after_round(0, queues);
+        tracer
+    }
+
+    fn record(&mut self, id: usize, hop: Hop) {
+        self.items[id].hops.push(hop);
+    }
+
+    fn after_round<W: Worry>(&mut self, round: usize, queues: &[Vec<Item<W>>]) {
+        if !self.snapshot_rounds.contains(&round) {
+            return;
+        }
+        let queues = queues
+            .iter()
+            .map(|queue| {
+                queue
+                    .iter()
+                    .map(|it| json!({"id": it.id, "worry": it.worry.to_json()}))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        self.snapshots.push((round, json!(queues)));
+    }
+
+    fn to_json(&self) -> Value {
+        let items = self
+            .items
+            .iter()
+            .enumerate()
+            .map(|(id, it)| {
+                let hops = it
+                    .hops
+                    .iter()
+                    .map(|hop| {
+                        json!({
+                            "round": hop.round,
+                            "monkey": hop.monkey,
+                            "worry": hop.worry,
+                            "target": hop.target,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                json!({
+                    "id": id,
+                    "start_monkey": it.start_monkey,
+                    "start_worry": it.start_worry,
+                    "hops": hops,
+                })
+            })
+            .collect::<Vec<_>>();
+        let snapshots = self
+            .snapshots
+            .iter()
+            .map(|(round, queues)| json!({"round": round, "queues": queues}))
+            .collect::<Vec<_>>();
+        json!({"items": items, "snapshots": snapshots})
+    }
+}
+
+fn run_one_round<W: Worry>(
+    monkeys: &[Monkey],
+    queues: &mut [Vec<Item<W>>],
+    worry_management_fn: impl Fn(W) -> W,
+    round: usize,
+    mut tracer: Option<&mut Tracer>,
+) -> Result<HashMap<usize, usize>, WorryError> {
     let mut inspections = HashMap::new();
     for i in 0..monkeys.len() {
-        let mut items = vec![];
         assert!(monkeys[i].index == i);
-        std::mem::swap(&mut monkeys[i].starting_items, &mut items);
+        let items = std::mem::take(&mut queues[i]);
         inspections.insert(i, items.len());
         for item in items {
-            let mut new_worry = eval_worry_transform(item, monkeys[i].transform);
-            new_worry = worry_management_fn(new_worry);
-            let target_index = if new_worry % monkeys[i].test_divisor == 0 {
+            let new_worry = worry_management_fn(monkeys[i].transform.eval(&item.worry)?);
+            let target_index = if new_worry.is_multiple_of(monkeys[i].test_divisor) {
                 monkeys[i].true_target_index
             } else {
                 monkeys[i].false_target_index
             };
             assert!(monkeys[target_index].index == target_index);
-            monkeys[target_index].starting_items.push(new_worry);
+            if let Some(tracer) = tracer.as_deref_mut() {
+                tracer.record(
+                    item.id,
+                    Hop {
+                        round,
+                        monkey: i,
+                        worry: new_worry.to_json(),
+                        target: target_index,
+                    },
+                );
+            }
+            queues[target_index].push(Item {
+                id: item.id,
+                worry: new_worry,
+            });
         }
     }
-    inspections
+    Ok(inspections)
 }
 
-fn part1() {
-    let mut monkeys: Vec<Monkey> = read_blank_line_delimited_blocks_as(11);
+fn simulate<W: Worry>(
+    monkeys: &[Monkey],
+    rounds: usize,
+    worry_management_fn: impl Fn(W) -> W,
+) -> Result<HashMap<usize, usize>, WorryError> {
+    let mut queues = initial_queues(monkeys);
+    run_rounds(monkeys, &mut queues, rounds, worry_management_fn, None)
+}
+
+fn run_rounds<W: Worry>(
+    monkeys: &[Monkey],
+    queues: &mut [Vec<Item<W>>],
+    rounds: usize,
+    worry_management_fn: impl Fn(W) -> W,
+    mut tracer: Option<&mut Tracer>,
+) -> Result<HashMap<usize, usize>, WorryError> {
     let mut total_inspections = HashMap::new();
-    for _ in 0..20 {
-        let inspections = run_one_round(&mut monkeys, |worry| worry / 3);
+    for round in 1..=rounds {
+        let inspections = run_one_round(
+            monkeys,
+            queues,
+            &worry_management_fn,
+            round,
+            tracer.as_deref_mut(),
+        )?;
         update_inspection_count(&mut total_inspections, inspections);
+        if let Some(tracer) = tracer.as_deref_mut() {
+            tracer.after_round(round, queues);
+        }
+    }
+    Ok(total_inspections)
+}
+
+fn monkey_business(inspections: &HashMap<usize, usize>) -> u128 {
+    inspections
+        .values()
+        .sorted()
+        .rev()
+        .take(2)
+        .map(|it| *it as u128)
+        .product()
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn monkey_lcm(monkeys: &[Monkey]) -> u64 {
+    monkeys
+        .iter()
+        .map(|it| it.test_divisor)
+        .fold(1, |acc, d| acc / gcd(acc, d) * d)
+}
+
+// The lcm of the test divisors, as long as every operation is compatible with working modulo it.
+fn modular_lcm(monkeys: &[Monkey]) -> Result<u64, ModularError> {
+    for monkey in monkeys {
+        if let Some(op) = monkey.transform.non_modular_op() {
+            return Err(ModularError::Unsupported {
+                monkey: monkey.index,
+                op,
+            });
+        }
+    }
+    Ok(monkey_lcm(monkeys))
+}
+
+fn part1() {
+    let monkeys: Vec<Monkey> = read_blank_line_delimited_blocks_as(11);
+    match simulate(&monkeys, 20, |worry: u64| worry / 3) {
+        Ok(inspections) => println!("Part 1: {}", monkey_business(&inspections)),
+        Err(err) => println!("Part 1 failed: {err}"),
     }
-    let monkey_business: usize = total_inspections.values().sorted().rev().take(2).product();
-    println!("Part 1: {monkey_business}");
 }
 
 fn part2() {
-    let mut monkeys: Vec<Monkey> = read_blank_line_delimited_blocks_as(11);
-    let monkey_divisors: HashSet<u64> = monkeys.iter().map(|it| it.test_divisor).collect();
-    let monkey_lcm: u64 = monkey_divisors.iter().product();
-    let mut total_inspections = HashMap::new();
-    for _ in 0..10000 {
-        let inspections = run_one_round(&mut monkeys, |worry| worry % monkey_lcm);
-        update_inspection_count(&mut total_inspections, inspections);
+    let monkeys: Vec<Monkey> = read_blank_line_delimited_blocks_as(11);
+    let result = modular_lcm(&monkeys)
+        .and_then(|lcm| Ok(simulate(&monkeys, 10000, |worry: u64| worry % lcm)?));
+    match result {
+        Ok(inspections) => println!("Part 2: {}", monkey_business(&inspections)),
+        Err(err) => println!("Part 2 failed: {err}"),
     }
-    let monkey_business: usize = total_inspections.values().sorted().rev().take(2).product();
-    println!("Part 2: {monkey_business}");
+}
+
+// Simulates with exact arbitrary-precision worry levels and no reduction at all. Exact worry
+// levels grow very quickly, so this is only practical for small round counts.
+fn exact(rounds: usize) {
+    let monkeys: Vec<Monkey> = read_blank_line_delimited_blocks_as(11);
+    match simulate(&monkeys, rounds, |worry: BigUint| worry) {
+        Ok(inspections) => println!(
+            "Exact over {rounds} rounds: monkey business {}",
+            monkey_business(&inspections)
+        ),
+        Err(err) => println!("Exact simulation failed: {err}"),
+    }
+}
+
+// Moves a single item through one round, counting its inspections. It stays with the round as
+// long as it is thrown to higher-numbered monkeys, which haven't had their turn yet.
+fn advance_item_one_round(
+    monkeys: &[Monkey],
+    lcm: u64,
+    (mut monkey, mut worry): (usize, u64),
+    counts: &mut [usize],
+) -> Result<(usize, u64), WorryError> {
+    loop {
+        counts[monkey] += 1;
+        worry = monkeys[monkey].transform.eval(&worry)? % lcm;
+        let target = if worry.is_multiple_of(monkeys[monkey].test_divisor) {
+            monkeys[monkey].true_target_index
+        } else {
+            monkeys[monkey].false_target_index
+        };
+        if target <= monkey {
+            return Ok((target, worry));
+        }
+        monkey = target;
+    }
+}
+
+fn advance_item(
+    monkeys: &[Monkey],
+    lcm: u64,
+    mut state: (usize, u64),
+    rounds: u64,
+    counts: &mut [usize],
+) -> Result<(usize, u64), WorryError> {
+    for _ in 0..rounds {
+        state = advance_item_one_round(monkeys, lcm, state, counts)?;
+    }
+    Ok(state)
+}
+
+// Items never interact, and an item's (monkey, worry mod lcm) at the start of a round fully
+// determines its future, so each item's per-round state eventually repeats. Brent's algorithm
+// finds where the cycle starts and how long it is without remembering the states on the way;
+// the inspections for any number of rounds then follow from one lap of the cycle.
+fn item_inspections(
+    monkeys: &[Monkey],
+    lcm: u64,
+    start: (usize, u64),
+    rounds: u64,
+) -> Result<Vec<usize>, WorryError> {
+    let mut counts = vec![0; monkeys.len()];
+    let mut scratch = vec![0; monkeys.len()];
+
+    let (mut power, mut cycle_len) = (1, 1);
+    let mut tortoise = start;
+    let mut hare = advance_item(monkeys, lcm, start, 1, &mut scratch)?;
+    let mut explored = 1;
+    while tortoise != hare {
+        if explored >= rounds {
+            advance_item(monkeys, lcm, start, rounds, &mut counts)?;
+            return Ok(counts);
+        }
+        if power == cycle_len {
+            tortoise = hare;
+            power *= 2;
+            cycle_len = 0;
+        }
+        hare = advance_item(monkeys, lcm, hare, 1, &mut scratch)?;
+        cycle_len += 1;
+        explored += 1;
+    }
+
+    let mut tortoise = start;
+    let mut hare = advance_item(monkeys, lcm, start, cycle_len, &mut scratch)?;
+    let mut cycle_start = 0;
+    while tortoise != hare {
+        tortoise = advance_item(monkeys, lcm, tortoise, 1, &mut scratch)?;
+        hare = advance_item(monkeys, lcm, hare, 1, &mut scratch)?;
+        cycle_start += 1;
+    }
+
+    if rounds <= cycle_start {
+        advance_item(monkeys, lcm, start, rounds, &mut counts)?;
+        return Ok(counts);
+    }
+    let cycle_entry = advance_item(monkeys, lcm, start, cycle_start, &mut counts)?;
+    let mut lap = vec![0; monkeys.len()];
+    advance_item(monkeys, lcm, cycle_entry, cycle_len, &mut lap)?;
+    let laps = (rounds - cycle_start) / cycle_len;
+    for (count, per_lap) in counts.iter_mut().zip(&lap) {
+        *count += laps as usize * per_lap;
+    }
+    advance_item(
+        monkeys,
+        lcm,
+        cycle_entry,
+        (rounds - cycle_start) % cycle_len,
+        &mut counts,
+    )?;
+    Ok(counts)
+}
+
+fn fast_forward(monkeys: &[Monkey], rounds: u64) -> Result<HashMap<usize, usize>, ModularError> {
+    let lcm = modular_lcm(monkeys)?;
+    let mut total = vec![0; monkeys.len()];
+    for (monkey, it) in monkeys.iter().enumerate() {
+        for worry in &it.starting_items {
+            let counts = item_inspections(monkeys, lcm, (monkey, worry % lcm), rounds)?;
+            for (sum, count) in total.iter_mut().zip(counts) {
+                *sum += count;
+            }
+        }
+    }
+    Ok(total.into_iter().enumerate().collect())
+}
+
+fn huge_rounds() {
+    let monkeys: Vec<Monkey> = read_blank_line_delimited_blocks_as(11);
+    let rounds = 1_000_000_000_000;
+    match fast_forward(&monkeys, rounds) {
+        Ok(inspections) => println!(
+            "After {rounds} rounds: monkey business {}",
+            monkey_business(&inspections)
+        ),
+        Err(err) => println!("Fast-forward failed: {err}"),
+    }
+}
+
+fn trace(rounds: usize, snapshot_rounds: Vec<usize>, path: &Path) {
+    let monkeys: Vec<Monkey> = read_blank_line_delimited_blocks_as(11);
+    let lcm = match modular_lcm(&monkeys) {
+        Ok(lcm) => lcm,
+        Err(err) => {
+            println!("Cannot trace: {err}");
+            return;
+        }
+    };
+    let mut queues = initial_queues(&monkeys);
+    let mut tracer = Tracer::new(&queues, snapshot_rounds);
+    run_rounds(
+        &monkeys,
+        &mut queues,
+        rounds,
+        |worry: u64| worry % lcm,
+        Some(&mut tracer),
+    )
+    .unwrap();
+    std::fs::write(
+        path,
+        serde_json::to_string_pretty(&tracer.to_json()).unwrap(),
+    )
+    .unwrap();
 }
 
 fn main() {
     part1();
     part2();
+    huge_rounds();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|it| it.as_str()).collect_vec()[..] {
+        ["trace", rounds, snapshot_rounds, path] => trace(
+            rounds.parse().unwrap(),
+            snapshot_rounds
+                .split(',')
+                .map(|it| it.parse().unwrap())
+                .collect(),
+            Path::new(path),
+        ),
+        ["exact", rounds] => exact(rounds.parse().unwrap()),
+        [] => {}
+        _ => panic!(
+            "Usage: day11 [exact <rounds> | trace <rounds> <snapshot rounds, comma separated> <out.json>]"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modular_and_exact_simulations_agree() {
+        let monkeys: Vec<Monkey> = read_blank_line_delimited_blocks_as(11);
+        let lcm = modular_lcm(&monkeys).unwrap();
+        let modular = simulate(&monkeys, 200, |worry: u64| worry % lcm).unwrap();
+        let exact = simulate(&monkeys, 200, |worry: BigUint| worry).unwrap();
+        assert_eq!(modular, exact);
+    }
+
+    #[test]
+    fn fast_forward_matches_direct_simulation() {
+        let monkeys: Vec<Monkey> = read_blank_line_delimited_blocks_as(11);
+        let lcm = modular_lcm(&monkeys).unwrap();
+        for rounds in [1, 20, 1000, 10000] {
+            let direct = simulate(&monkeys, rounds, |worry: u64| worry % lcm).unwrap();
+            let extrapolated = fast_forward(&monkeys, rounds as u64).unwrap();
+            assert_eq!(direct, extrapolated, "disagrees at {rounds} rounds");
+        }
+    }
 }
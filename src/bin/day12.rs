@@ -1,6 +1,7 @@
 use adventofcode2022::read_input_lines;
 
 // (row, col) indexes of start and end and grid
+#[allow(clippy::type_complexity)]
 fn parse_input() -> ((usize, usize), (usize, usize), Vec<Vec<u8>>) {
     let lines = read_input_lines(12, false);
     let mut output = vec![];
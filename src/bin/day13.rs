@@ -1,96 +1,525 @@
 use std::cmp::Ordering;
 use std::convert::Infallible;
+use std::fmt::Display;
 use std::iter::zip;
 use std::str::FromStr;
 
-use adventofcode2022::read_blank_line_delimited_blocks_as;
+use adventofcode2022::{read_blank_line_delimited_blocks_as, read_input_lines};
+use serde_json::Value;
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone)]
 enum PacketPart {
     List(Vec<PacketPart>),
     Number(i32),
 }
 
-fn compare(lhs: &PacketPart, rhs: &PacketPart) -> Ordering {
-    use PacketPart::*;
-    match (lhs, rhs) {
-        (Number(l), Number(r)) if *l < *r => Ordering::Less,
-        (Number(l), Number(r)) if *l > *r => Ordering::Greater,
-        (Number(_), Number(_)) => Ordering::Equal,
-        (Number(l), List(_)) => compare(&PacketPart::List(vec![PacketPart::Number(*l)]), rhs),
-        (List(_), Number(r)) => compare(lhs, &PacketPart::List(vec![PacketPart::Number(*r)])),
-        (List(l), List(r)) => {
-            for (l_item, r_item) in zip(l, r) {
-                match compare(l_item, r_item) {
-                    Ordering::Less => return Ordering::Less,
-                    Ordering::Greater => return Ordering::Greater,
-                    Ordering::Equal => (),
-                }
-            }
+// Compares a lone item against a list as if the item were wrapped in a one-element list.
+fn compare_wrapped(item: &PacketPart, list: &[PacketPart]) -> Ordering {
+    match list.first() {
+        None => Ordering::Greater,
+        Some(first) => item.cmp(first).then(if list.len() > 1 {
+            Ordering::Less
+        } else {
+            Ordering::Equal
+        }),
+    }
+}
 
-            if l.len() < r.len() {
-                Ordering::Less
-            } else if r.len() < l.len() {
-                Ordering::Greater
-            } else {
-                Ordering::Equal
+impl Ord for PacketPart {
+    fn cmp(&self, other: &Self) -> Ordering {
+        use PacketPart::*;
+        match (self, other) {
+            (Number(l), Number(r)) => l.cmp(r),
+            (Number(_), List(r)) => compare_wrapped(self, r),
+            (List(l), Number(_)) => compare_wrapped(other, l).reverse(),
+            (List(l), List(r)) => l.cmp(r),
+        }
+    }
+}
+
+impl PartialOrd for PacketPart {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Packets that order as equal are equal, e.g. `[[1]]` and `[1]`.
+impl PartialEq for PacketPart {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PacketPart {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Reason {
+    Numbers(i32, i32),
+    RanOut(Side),
+    Equal,
+}
+
+#[derive(Clone, Debug)]
+struct Explanation {
+    ordering: Ordering,
+    path: Vec<usize>,
+    reason: Reason,
+    wrapped: Vec<(Side, Vec<usize>)>,
+}
+
+fn format_path(path: &[usize]) -> String {
+    if path.is_empty() {
+        "the top level".to_string()
+    } else {
+        path.iter().map(|it| format!("[{it}]")).collect()
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = format_path(&self.path);
+        match self.reason {
+            Reason::Numbers(l, r) => {
+                write!(f, "{l} {} {r} at {path}", if l < r { "<" } else { ">" })?
             }
+            Reason::RanOut(Side::Left) => write!(f, "left ran out of items at {path}")?,
+            Reason::RanOut(Side::Right) => write!(f, "right ran out of items at {path}")?,
+            Reason::Equal => write!(f, "packets are equal")?,
+        }
+        for (side, at) in &self.wrapped {
+            let side = if *side == Side::Left { "left" } else { "right" };
+            write!(
+                f,
+                " (wrapped {side} number at {} in a list)",
+                format_path(at)
+            )?;
         }
+        Ok(())
     }
 }
 
-impl std::fmt::Debug for PacketPart {
+fn explain_lists(
+    lhs: &[PacketPart],
+    rhs: &[PacketPart],
+    path: &mut Vec<usize>,
+    wrapped: &mut Vec<(Side, Vec<usize>)>,
+) -> Option<(Ordering, Reason)> {
+    for (ix, (l, r)) in zip(lhs, rhs).enumerate() {
+        path.push(ix);
+        if let Some(decision) = explain_at(l, r, path, wrapped) {
+            return Some(decision);
+        }
+        path.pop();
+    }
+    match lhs.len().cmp(&rhs.len()) {
+        Ordering::Less => Some((Ordering::Less, Reason::RanOut(Side::Left))),
+        Ordering::Greater => Some((Ordering::Greater, Reason::RanOut(Side::Right))),
+        Ordering::Equal => None,
+    }
+}
+
+fn explain_at(
+    lhs: &PacketPart,
+    rhs: &PacketPart,
+    path: &mut Vec<usize>,
+    wrapped: &mut Vec<(Side, Vec<usize>)>,
+) -> Option<(Ordering, Reason)> {
+    use PacketPart::*;
+    let (side, l, r) = match (lhs, rhs) {
+        (Number(l), Number(r)) => {
+            return (l != r).then(|| (l.cmp(r), Reason::Numbers(*l, *r)));
+        }
+        (List(l), List(r)) => return explain_lists(l, r, path, wrapped),
+        (Number(_), List(r)) => (Side::Left, std::slice::from_ref(lhs), &r[..]),
+        (List(l), Number(_)) => (Side::Right, &l[..], std::slice::from_ref(rhs)),
+    };
+    wrapped.push((side, path.clone()));
+    let decision = explain_lists(l, r, path, wrapped);
+    if decision.is_none() {
+        wrapped.pop();
+    }
+    decision
+}
+
+// Reports where and why two packets order the way they do.
+fn explain(lhs: &PacketPart, rhs: &PacketPart) -> Explanation {
+    let mut path = vec![];
+    let mut wrapped = vec![];
+    let (ordering, reason) =
+        explain_at(lhs, rhs, &mut path, &mut wrapped).unwrap_or((Ordering::Equal, Reason::Equal));
+    Explanation {
+        ordering,
+        path,
+        reason,
+        wrapped,
+    }
+}
+
+impl Display for PacketPart {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::List(v) => write!(f, "{:?}", v),
-            Self::Number(n) => write!(f, "{}", n),
+            Self::List(v) => {
+                write!(f, "[")?;
+                for (ix, item) in v.iter().enumerate() {
+                    if ix > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Self::Number(n) => write!(f, "{n}"),
         }
     }
 }
 
-fn parse_packet_part<'a>(mut s: &'a str, curr: &mut Vec<PacketPart>) -> &'a str {
-    if s.is_empty() {
-        return s;
+impl std::fmt::Debug for PacketPart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
     }
-    if s.starts_with('[') {
-        let mut next_vec = vec![];
-        s = parse_packet_part(&s[1..s.len()], &mut next_vec);
-        let next_list = PacketPart::List(next_vec);
-        curr.push(next_list);
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PacketErrorKind {
+    UnexpectedEnd,
+    UnexpectedChar(char),
+    UnmatchedClose,
+    MissingValue,
+    NumberOutOfRange,
+    TrailingInput,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct PacketParseError {
+    position: usize,
+    kind: PacketErrorKind,
+}
+
+impl Display for PacketParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at position {}: ", self.position)?;
+        match self.kind {
+            PacketErrorKind::UnexpectedEnd => write!(f, "unexpected end of packet"),
+            PacketErrorKind::UnexpectedChar(c) => write!(f, "unexpected character {c:?}"),
+            PacketErrorKind::UnmatchedClose => write!(f, "']' without a matching '['"),
+            PacketErrorKind::MissingValue => write!(f, "expected a value after ','"),
+            PacketErrorKind::NumberOutOfRange => write!(f, "number out of range"),
+            PacketErrorKind::TrailingInput => write!(f, "unexpected input after the packet"),
+        }
     }
-    if s.starts_with(|c: char| c.is_ascii_digit()) {
-        let num = s
-            .chars()
-            .take_while(|c| c.is_ascii_digit())
-            .collect::<String>();
-        let parsed: i32 = num.parse().unwrap();
-        curr.push(PacketPart::Number(parsed));
-        return parse_packet_part(&s[num.len()..s.len()], curr);
+}
+
+struct PacketParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl PacketParser<'_> {
+    fn error(&self, kind: PacketErrorKind) -> PacketParseError {
+        PacketParseError {
+            position: self.pos,
+            kind,
+        }
     }
-    if s.starts_with(',') {
-        return parse_packet_part(&s[1..s.len()], curr);
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
     }
-    if s.starts_with(']') {
-        return &s[1..s.len()];
+
+    fn parse_value(&mut self) -> Result<PacketPart, PacketParseError> {
+        match self.peek() {
+            None => Err(self.error(PacketErrorKind::UnexpectedEnd)),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(PacketPart::List(items));
+                }
+                loop {
+                    items.push(self.parse_value()?);
+                    match self.peek() {
+                        Some(b',') => {
+                            self.pos += 1;
+                            if self.peek() == Some(b']') {
+                                return Err(self.error(PacketErrorKind::MissingValue));
+                            }
+                        }
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(PacketPart::List(items));
+                        }
+                        None => return Err(self.error(PacketErrorKind::UnexpectedEnd)),
+                        Some(c) => {
+                            return Err(self.error(PacketErrorKind::UnexpectedChar(c as char)))
+                        }
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.pos;
+                while self.peek().is_some_and(|it| it.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                let digits = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+                digits
+                    .parse()
+                    .map(PacketPart::Number)
+                    .map_err(|_| PacketParseError {
+                        position: start,
+                        kind: PacketErrorKind::NumberOutOfRange,
+                    })
+            }
+            Some(b']') => Err(self.error(PacketErrorKind::UnmatchedClose)),
+            Some(c) => Err(self.error(PacketErrorKind::UnexpectedChar(c as char))),
+        }
     }
-    panic!("Unexpected state: {s}");
 }
 
 impl FromStr for PacketPart {
-    type Err = Infallible;
+    type Err = PacketParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut root_vec = vec![];
-        assert!(s.starts_with('['));
-        let remainder = parse_packet_part(&s[1..s.len()], &mut root_vec);
-        if !remainder.is_empty() {
-            panic!("Got leftover characters: {remainder}");
+        let mut parser = PacketParser {
+            input: s.as_bytes(),
+            pos: 0,
+        };
+        // A bare number is a valid list item but not a packet.
+        if let Some(c) = parser.peek().filter(|it| it.is_ascii_digit()) {
+            return Err(parser.error(PacketErrorKind::UnexpectedChar(c as char)));
+        }
+        let packet = parser.parse_value()?;
+        match parser.peek() {
+            None => Ok(packet),
+            Some(b']') => Err(parser.error(PacketErrorKind::UnmatchedClose)),
+            Some(_) => Err(parser.error(PacketErrorKind::TrailingInput)),
+        }
+    }
+}
+
+impl From<&PacketPart> for Value {
+    fn from(packet: &PacketPart) -> Value {
+        match packet {
+            PacketPart::List(v) => Value::Array(v.iter().map(Value::from).collect()),
+            PacketPart::Number(n) => Value::from(*n),
         }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct PacketJsonError(String);
+
+fn part_from_json(value: &Value) -> Result<PacketPart, PacketJsonError> {
+    match value {
+        Value::Array(items) => Ok(PacketPart::List(
+            items.iter().map(part_from_json).collect::<Result<_, _>>()?,
+        )),
+        // The text form has no sign, so negative numbers couldn't round-trip.
+        Value::Number(n) => n
+            .as_u64()
+            .and_then(|it| i32::try_from(it).ok())
+            .map(PacketPart::Number)
+            .ok_or_else(|| PacketJsonError(format!("{n} is not a packet integer"))),
+        other => Err(PacketJsonError(format!("{other} is not a packet value"))),
+    }
+}
+
+// Like the text form, a packet must be a list at the top level.
+impl TryFrom<&Value> for PacketPart {
+    type Error = PacketJsonError;
 
-        Ok(PacketPart::List(root_vec))
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Array(_) => part_from_json(value),
+            other => Err(PacketJsonError(format!("{other} is not a list"))),
+        }
     }
 }
 
+// A list token records how many items it directly holds and how many tokens its whole
+// subtree spans, so a list can be skipped over without walking it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    List { len: u32, span: u32 },
+    Number(i32),
+}
+
+impl Token {
+    fn span(&self) -> usize {
+        match self {
+            Token::List { span, .. } => *span as usize,
+            Token::Number(_) => 1,
+        }
+    }
+}
+
+// Many packets flattened into a single token array.
+#[derive(Default)]
+struct PacketArena {
+    tokens: Vec<Token>,
+    packets: Vec<std::ops::Range<usize>>,
+}
+
+impl PacketArena {
+    fn push_str(&mut self, s: &str) -> Result<usize, PacketParseError> {
+        let start = self.tokens.len();
+        let result = self.parse_into(s);
+        if result.is_err() {
+            self.tokens.truncate(start);
+        }
+        result?;
+        self.packets.push(start..self.tokens.len());
+        Ok(self.packets.len() - 1)
+    }
+
+    fn parse_into(&mut self, s: &str) -> Result<(), PacketParseError> {
+        let input = s.as_bytes();
+        let error = |position, kind| PacketParseError { position, kind };
+        let mut open: Vec<usize> = vec![];
+        let mut expect_value = true;
+        let mut done = false;
+        let mut pos = 0;
+        while pos < input.len() {
+            let c = input[pos];
+            if done {
+                let kind = if c == b']' {
+                    PacketErrorKind::UnmatchedClose
+                } else {
+                    PacketErrorKind::TrailingInput
+                };
+                return Err(error(pos, kind));
+            }
+            match c {
+                // Numbers are only valid inside a list; a packet itself is always a list.
+                b'[' | b'0'..=b'9' if expect_value && (c == b'[' || !open.is_empty()) => {
+                    if let Some(parent) = open.last() {
+                        if let Token::List { len, .. } = &mut self.tokens[*parent] {
+                            *len += 1;
+                        }
+                    }
+                    if c == b'[' {
+                        open.push(self.tokens.len());
+                        self.tokens.push(Token::List { len: 0, span: 0 });
+                        pos += 1;
+                    } else {
+                        let start = pos;
+                        while pos < input.len() && input[pos].is_ascii_digit() {
+                            pos += 1;
+                        }
+                        let n = s[start..pos]
+                            .parse()
+                            .map_err(|_| error(start, PacketErrorKind::NumberOutOfRange))?;
+                        self.tokens.push(Token::Number(n));
+                        expect_value = false;
+                        done = open.is_empty();
+                    }
+                }
+                b']' => {
+                    let list = open
+                        .pop()
+                        .ok_or(error(pos, PacketErrorKind::UnmatchedClose))?;
+                    let span = (self.tokens.len() - list) as u32;
+                    if let Token::List { len, span: s } = &mut self.tokens[list] {
+                        // A close right after a comma, e.g. `[1,]`, is missing a value.
+                        if expect_value && *len > 0 {
+                            return Err(error(pos, PacketErrorKind::MissingValue));
+                        }
+                        *s = span;
+                    }
+                    pos += 1;
+                    expect_value = false;
+                    done = open.is_empty();
+                }
+                b',' if !expect_value && !open.is_empty() => {
+                    expect_value = true;
+                    pos += 1;
+                }
+                other => return Err(error(pos, PacketErrorKind::UnexpectedChar(other as char))),
+            }
+        }
+        if !done {
+            return Err(error(input.len(), PacketErrorKind::UnexpectedEnd));
+        }
+        Ok(())
+    }
+
+    fn tape(&self, packet: usize) -> &[Token] {
+        &self.tokens[self.packets[packet].clone()]
+    }
+
+    fn compare(&self, lhs: usize, rhs: usize) -> Ordering {
+        compare_tapes(self.tape(lhs), self.tape(rhs))
+    }
+}
+
+// Walks both tapes in step with an explicit stack of open list pairs. A number compared to a
+// list is treated as a one-item list by giving its side of the frame just that one token.
+fn compare_tapes(lhs: &[Token], rhs: &[Token]) -> Ordering {
+    struct Frame {
+        l: usize,
+        l_left: u32,
+        r: usize,
+        r_left: u32,
+    }
+
+    let mut stack = vec![Frame {
+        l: 0,
+        l_left: 1,
+        r: 0,
+        r_left: 1,
+    }];
+    while let Some(frame) = stack.last_mut() {
+        match (frame.l_left, frame.r_left) {
+            (0, 0) => {
+                stack.pop();
+                continue;
+            }
+            (0, _) => return Ordering::Less,
+            (_, 0) => return Ordering::Greater,
+            _ => {}
+        }
+        let (l, r) = (frame.l, frame.r);
+        frame.l += lhs[l].span();
+        frame.l_left -= 1;
+        frame.r += rhs[r].span();
+        frame.r_left -= 1;
+        let next = match (lhs[l], rhs[r]) {
+            (Token::Number(a), Token::Number(b)) => {
+                if a != b {
+                    return a.cmp(&b);
+                }
+                continue;
+            }
+            (Token::List { len: a, .. }, Token::List { len: b, .. }) => Frame {
+                l: l + 1,
+                l_left: a,
+                r: r + 1,
+                r_left: b,
+            },
+            (Token::Number(_), Token::List { len: b, .. }) => Frame {
+                l,
+                l_left: 1,
+                r: r + 1,
+                r_left: b,
+            },
+            (Token::List { len: a, .. }, Token::Number(_)) => Frame {
+                l: l + 1,
+                l_left: a,
+                r,
+                r_left: 1,
+            },
+        };
+        stack.push(next);
+    }
+    Ordering::Equal
+}
+
 #[derive(Clone, Debug)]
 struct PacketPair(PacketPart, PacketPart);
 
@@ -98,7 +527,7 @@ impl FromStr for PacketPair {
     type Err = Infallible;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lines: Vec<&str> = s.split('\n').collect();
+        let lines: Vec<&str> = s.lines().collect();
         Ok(PacketPair(
             lines[0].parse().unwrap(),
             lines[1].parse().unwrap(),
@@ -110,7 +539,7 @@ fn part1() {
     let input: Vec<PacketPair> = read_blank_line_delimited_blocks_as(13);
     let mut index_sum: usize = 0;
     for (idx, pair) in input.iter().enumerate() {
-        if matches!(compare(&pair.0, &pair.1), Ordering::Less) {
+        if pair.0 < pair.1 {
             index_sum += idx + 1;
         }
     }
@@ -126,22 +555,128 @@ fn part2() {
     }
     let key_packet_1 = PacketPart::List(vec![PacketPart::List(vec![PacketPart::Number(2)])]);
     let key_packet_2 = PacketPart::List(vec![PacketPart::List(vec![PacketPart::Number(6)])]);
-    all_packets.push(key_packet_1.clone());
-    all_packets.push(key_packet_2.clone());
 
-    all_packets.sort_by(compare);
-
-    let mut decoder_key = 1;
-    for (i, packet) in all_packets.into_iter().enumerate() {
-        if packet == key_packet_1 || packet == key_packet_2 {
-            decoder_key *= i + 1;
-        }
-    }
+    // Each divider's index is one past the number of packets sorting before it, plus one for
+    // the first divider when placing the second.
+    let key_1_index = 1 + all_packets.iter().filter(|it| **it < key_packet_1).count();
+    let key_2_index = 2 + all_packets.iter().filter(|it| **it < key_packet_2).count();
+    let decoder_key = key_1_index * key_2_index;
 
     println!("Part 2: {decoder_key}");
 }
 
+// Redoes part 2 on the flattened representation.
+fn tape_part2() -> usize {
+    let mut arena = PacketArena::default();
+    for line in read_input_lines(13, false) {
+        arena.push_str(&line).unwrap();
+    }
+    let key_1 = arena.push_str("[[2]]").unwrap();
+    let key_2 = arena.push_str("[[6]]").unwrap();
+    let mut order: Vec<usize> = (0..arena.packets.len()).collect();
+    order.sort_by(|a, b| arena.compare(*a, *b));
+    let position = |key| order.iter().position(|it| *it == key).unwrap() + 1;
+    position(key_1) * position(key_2)
+}
+
 fn main() {
     part1();
     part2();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(|it| it.as_str()).collect::<Vec<_>>()[..] {
+        ["explain", lhs, rhs] => {
+            let (lhs, rhs): (PacketPart, PacketPart) = (lhs.parse().unwrap(), rhs.parse().unwrap());
+            let explanation = explain(&lhs, &rhs);
+            println!("{:?}: {explanation}", explanation.ordering);
+        }
+        ["tape"] => println!("Tape part 2: {}", tape_part2()),
+        ["json", packet] => match packet.parse::<PacketPart>() {
+            Ok(packet) => println!("{}", Value::from(&packet)),
+            Err(err) => println!("{packet}: {err}"),
+        },
+        ["from-json", json] => {
+            let value: Value = serde_json::from_str(json).expect("Invalid JSON.");
+            match PacketPart::try_from(&value) {
+                Ok(packet) => println!("{packet}"),
+                Err(PacketJsonError(err)) => println!("{json}: {err}"),
+            }
+        }
+        ref packets => {
+            for arg in packets {
+                match arg.parse::<PacketPart>() {
+                    Ok(packet) => println!("{packet}"),
+                    Err(err) => println!("{arg}: {err}"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_round_trip_through_text_and_json() {
+        let input: Vec<PacketPair> = read_blank_line_delimited_blocks_as(13);
+        for packet in input.iter().flat_map(|it| [&it.0, &it.1]) {
+            let text = packet.to_string();
+            assert_eq!(text.parse::<PacketPart>().unwrap().to_string(), text);
+            let json = Value::from(packet);
+            assert_eq!(serde_json::to_string(&json).unwrap(), text);
+            assert_eq!(PacketPart::try_from(&json).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn json_numbers_must_be_non_negative_packet_integers() {
+        for json in ["[-1,[2]]", "[1.5]", "[2147483648]"] {
+            let value: Value = serde_json::from_str(json).unwrap();
+            assert!(PacketPart::try_from(&value).is_err(), "{json} converted");
+        }
+        let value: Value = serde_json::from_str("[0,[2147483647]]").unwrap();
+        assert_eq!(
+            PacketPart::try_from(&value).unwrap().to_string(),
+            "[0,[2147483647]]"
+        );
+    }
+
+    #[test]
+    fn packets_must_be_lists() {
+        for text in ["5", "", "x", "]"] {
+            let mut arena = PacketArena::default();
+            let parsed = text.parse::<PacketPart>().err();
+            assert!(parsed.is_some(), "{text:?} parsed");
+            assert_eq!(parsed, arena.push_str(text).err(), "{text:?}");
+        }
+        let value: Value = serde_json::from_str("5").unwrap();
+        assert!(PacketPart::try_from(&value).is_err());
+    }
+
+    #[test]
+    fn tape_agrees_with_packet_ordering() {
+        let mut packets: Vec<PacketPart> = read_input_lines(13, false)
+            .iter()
+            .map(|it| it.parse().unwrap())
+            .collect();
+        let keys: Vec<PacketPart> = ["[[2]]", "[[6]]"].map(|it| it.parse().unwrap()).into();
+        packets.extend(keys.iter().cloned());
+        packets.sort();
+        let position = |key| packets.iter().position(|it| it == key).unwrap() + 1;
+        assert_eq!(tape_part2(), position(&keys[0]) * position(&keys[1]));
+    }
+
+    #[test]
+    fn deeply_nested_packets_compare_without_recursion() {
+        let mut arena = PacketArena::default();
+        let depth = 10000;
+        let deep = |n: i32| format!("{}{n}{}", "[".repeat(depth), "]".repeat(depth));
+        let shallow = arena.push_str(&deep(1)).unwrap();
+        let deeper = arena.push_str(&format!("[{}]", deep(1))).unwrap();
+        let larger = arena.push_str(&deep(2)).unwrap();
+        assert_eq!(arena.compare(shallow, larger), Ordering::Less);
+        assert_eq!(arena.compare(shallow, deeper), Ordering::Equal);
+        assert_eq!(arena.compare(larger, shallow), Ordering::Greater);
+    }
 }
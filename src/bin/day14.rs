@@ -1,10 +1,13 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
+use std::time::Duration;
 use std::{convert::Infallible, str::FromStr};
 
+use adventofcode2022::interval::IntervalSet;
 use adventofcode2022::read_input_lines_as;
 use itertools::Itertools;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 struct Point {
     x: i64,
     y: i64,
@@ -29,7 +32,7 @@ impl Point {
         match grid.at(point) {
             None => SettleResult::Abyss,
             Some(Location::Empty) => SettleResult::Moved { new: point },
-            Some(Location::Rock) | Some(Location::Sand) => SettleResult::Done { loc: self },
+            Some(_) => SettleResult::Done { loc: self },
         }
     }
 
@@ -114,18 +117,20 @@ impl FromStr for PointChain {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum Location {
     Rock,
     Sand,
+    Water,
+    Block,
     Empty,
 }
 
 impl PointChain {
-    fn draw(&self, grid: &mut [Vec<Location>], min_x: i64, min_y: i64) {
+    fn draw(&self, grid: &mut Grid, material: Location) {
         for (start, end) in self.0.iter().tuple_windows() {
             for point in start.iter_to(*end) {
-                grid[(point.y - min_y) as usize][(point.x - min_x) as usize] = Location::Rock;
+                grid.set_at(point, material);
             }
         }
     }
@@ -175,45 +180,53 @@ fn compute_ranges(chains: &[PointChain]) -> ((i64, i64), (i64, i64)) {
     )
 }
 
+// Only non-empty cells are stored. Without a floor, everything below `max_y` is the abyss;
+// with one, the floor row is rock at every x.
 struct Grid {
-    locations: Vec<Vec<Location>>,
-    min_x: i64,
-    min_y: i64,
+    cells: HashMap<Point, Location>,
+    max_y: i64,
+    floor_y: Option<i64>,
 }
 
 impl Grid {
     fn at(&self, loc: Point) -> Option<Location> {
-        if loc.x < self.min_x
-            || loc.y < self.min_y
-            || loc.x >= self.locations[0].len() as i64 + self.min_x
-            || loc.y >= self.locations.len() as i64 + self.min_y
-        {
-            None
-        } else {
-            Some(self.locations[(loc.y - self.min_y) as usize][(loc.x - self.min_x) as usize])
+        match self.floor_y {
+            Some(floor_y) if loc.y >= floor_y => Some(Location::Rock),
+            None if loc.y > self.max_y => None,
+            _ => Some(self.cells.get(&loc).copied().unwrap_or(Location::Empty)),
         }
     }
 
     fn set_at(&mut self, loc: Point, value: Location) {
-        if self.at(loc).is_some() {
-            self.locations[(loc.y - self.min_y) as usize][(loc.x - self.min_x) as usize] = value;
-        } else {
+        if self.at(loc).is_none() {
             panic!("Out of bounds set at {loc:?}");
         }
+        match value {
+            Location::Empty => self.cells.remove(&loc),
+            _ => self.cells.insert(loc, value),
+        };
     }
 }
 
 impl Display for Grid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in &self.locations {
+        let Some(min_x) = self.cells.keys().map(|it| it.x).min() else {
+            return Ok(());
+        };
+        let max_x = self.cells.keys().map(|it| it.x).max().unwrap();
+        let min_y = std::cmp::min(0, self.cells.keys().map(|it| it.y).min().unwrap());
+        let max_y = self.floor_y.unwrap_or(self.max_y);
+        for y in min_y..=max_y {
             writeln!(
                 f,
                 "{}",
-                row.iter()
-                    .map(|it| match it {
-                        Location::Empty => ' ',
-                        Location::Rock => '█',
-                        Location::Sand => 'o',
+                (min_x..=max_x)
+                    .map(|x| match self.at(Point { x, y }) {
+                        None | Some(Location::Empty) => ' ',
+                        Some(Location::Rock) => '█',
+                        Some(Location::Sand) => 'o',
+                        Some(Location::Water) => '~',
+                        Some(Location::Block) => '▒',
                     })
                     .join("")
             )?;
@@ -223,50 +236,28 @@ impl Display for Grid {
 }
 
 fn build_start_grid(chains: &[PointChain], include_floor: bool) -> Grid {
-    let ((mut min_x, mut max_x), (mut min_y, mut max_y)) = compute_ranges(chains);
-    // Because the sand enters from (500, 0), we have to adjust to ensure that point is in range.
-    // Also adjust x so that we have at least 502 spaces to the left and right for floor.
-    min_y = std::cmp::min(0, min_y);
-    max_y = std::cmp::max(0, max_y + 2);
-    min_x = std::cmp::min(min_x, -2);
-    max_x = std::cmp::max(max_x, 1002);
-
-    let mut grid = vec![];
-    for y in min_y..=max_y {
-        grid.push(vec![]);
-        for _ in min_x..=max_x {
-            grid[(y - min_y) as usize].push(Location::Empty);
-        }
-    }
-
+    let (_, (_, max_y)) = compute_ranges(chains);
+    let mut grid = Grid {
+        cells: HashMap::new(),
+        max_y,
+        floor_y: include_floor.then_some(max_y + 2),
+    };
     for chain in chains {
-        chain.draw(&mut grid, min_x, min_y);
-    }
-
-    if include_floor {
-        let floor_chain = PointChain(vec![
-            Point { x: min_x, y: max_y },
-            Point { x: max_x, y: max_y },
-        ]);
-        floor_chain.draw(&mut grid, min_x, min_y);
-    }
-
-    Grid {
-        locations: grid,
-        min_x,
-        min_y,
+        chain.draw(&mut grid, Location::Rock);
     }
+    grid
 }
 
-fn part1() {
-    let input = read_input_lines_as::<PointChain>(14);
-    let mut grid = build_start_grid(&input, false);
+// Drops grains from `source` until one falls into the abyss or the source itself is covered,
+// returning how many came to rest. Only used as the reference for the faster solvers below.
+#[cfg(test)]
+fn pour_sand(grid: &mut Grid, source: Point) -> u64 {
     let mut count: u64 = 0;
 
-    'outer: loop {
-        let mut new_grain = Point { x: 500, y: 0 };
+    'outer: while matches!(grid.at(source), Some(Location::Empty)) {
+        let mut new_grain = source;
         loop {
-            match new_grain.settle(&grid) {
+            match new_grain.settle(grid) {
                 SettleResult::Moved { new } => {
                     new_grain = new;
                 }
@@ -283,40 +274,358 @@ fn part1() {
         count += 1;
     }
 
-    println!("Part 1: {count}");
+    count
 }
-fn part2() {
-    let input = read_input_lines_as::<PointChain>(14);
-    let mut grid = build_start_grid(&input, true);
+
+// Same result as dropping grains one at a time from the source, but keeps the path the previous
+// grain fell along: the next grain follows it exactly until the cell where the last one came to
+// rest, so it can start from there.
+fn pour_sand_memoized(grid: &mut Grid, source: Point) -> u64 {
     let mut count: u64 = 0;
+    let mut path = vec![];
+    if matches!(grid.at(source), Some(Location::Empty)) {
+        path.push(source);
+    }
 
-    loop {
-        let mut new_grain = Point { x: 500, y: 0 };
-        loop {
-            match new_grain.settle(&grid) {
-                SettleResult::Moved { new } => {
-                    new_grain = new;
-                }
-                SettleResult::Done { loc } => {
-                    grid.set_at(loc, Location::Sand);
-                    break;
-                }
-                SettleResult::Abyss => {
-                    panic!("No abyss expected for part 2");
-                }
+    while let Some(&curr) = path.last() {
+        match curr.settle(grid) {
+            SettleResult::Moved { new } => path.push(new),
+            SettleResult::Done { loc } => {
+                grid.set_at(loc, Location::Sand);
+                path.pop();
+                count += 1;
             }
+            SettleResult::Abyss => break,
         }
+    }
 
-        count += 1;
+    count
+}
+
+// With a floor, sand ends up filling every cell reachable from the source by moving down,
+// down-left or down-right, so the answer can be counted a row at a time.
+fn count_reachable_with_floor(grid: &Grid, source: Point) -> u128 {
+    let floor_y = grid
+        .floor_y
+        .expect("Counting reachable cells requires a floor.");
+    if grid.at(source) != Some(Location::Empty) {
+        return 0;
+    }
+    let mut rocks: HashMap<i64, Vec<i64>> = HashMap::new();
+    for (point, location) in &grid.cells {
+        if matches!(location, Location::Rock) {
+            rocks.entry(point.y).or_default().push(point.x);
+        }
+    }
+    let remove_rocks = |row: &mut IntervalSet<i64>, y: i64| {
+        for x in rocks.get(&y).into_iter().flatten() {
+            row.remove(*x..=*x);
+        }
+    };
 
-        if matches!(grid.at(Point { x: 500, y: 0 }), Some(Location::Sand)) {
+    let mut row = IntervalSet::single(source.x..=source.x);
+    remove_rocks(&mut row, source.y);
+    let mut count = row.size();
+    for y in (source.y + 1)..floor_y {
+        if row.is_empty() {
             break;
         }
+        let mut next: IntervalSet<i64> = row
+            .ranges()
+            .iter()
+            .map(|it| (it.start() - 1)..=(it.end() + 1))
+            .collect();
+        remove_rocks(&mut next, y);
+        count += next.size();
+        row = next;
+    }
+    count
+}
+
+struct Particle {
+    pos: Point,
+    material: Location,
+    dir: i64,
+    reversed: bool,
+}
+
+struct Source {
+    at: Point,
+    material: Location,
+    every: usize,
+}
+
+// Sand behaves as in the puzzle. Water falls the same way, but when it can't go down it flows
+// sideways, turning around at most once before coming to rest. Blocks are solid until removed,
+// at which point anything resting on them starts moving again.
+struct Cave {
+    grid: Grid,
+    sources: Vec<Source>,
+    removals: Vec<(usize, Point)>,
+    particles: Vec<Particle>,
+    pending: Vec<Particle>,
+    moving: HashSet<Point>,
+    step: usize,
+    lost: usize,
+}
+
+impl Cave {
+    fn new(grid: Grid) -> Cave {
+        Cave {
+            grid,
+            sources: vec![],
+            removals: vec![],
+            particles: vec![],
+            pending: vec![],
+            moving: HashSet::new(),
+            step: 0,
+            lost: 0,
+        }
+    }
+
+    fn activate(&mut self, particle: Particle) {
+        self.moving.insert(particle.pos);
+        self.particles.push(particle);
+    }
+
+    fn wake(&mut self, pos: Point, dir: i64) {
+        if self.moving.contains(&pos) {
+            return;
+        }
+        if let Some(material @ (Location::Sand | Location::Water)) = self.grid.at(pos) {
+            self.activate(Particle {
+                pos,
+                material,
+                dir,
+                reversed: false,
+            });
+        }
     }
 
+    // Empties `pos` and sets moving anything that could fall or flow into it.
+    fn vacate(&mut self, pos: Point) {
+        self.grid.set_at(pos, Location::Empty);
+        for dx in [-1, 0, 1] {
+            self.wake(
+                Point {
+                    x: pos.x + dx,
+                    y: pos.y - 1,
+                },
+                -dx,
+            );
+        }
+        for dx in [-1, 1] {
+            let side = Point {
+                x: pos.x + dx,
+                y: pos.y,
+            };
+            if self.grid.at(side) == Some(Location::Water) {
+                self.wake(side, -dx);
+            }
+        }
+    }
+
+    fn remove_block(&mut self, pos: Point) -> bool {
+        if self.grid.at(pos) != Some(Location::Block) {
+            return false;
+        }
+        self.vacate(pos);
+        true
+    }
+
+    fn move_particle(&mut self, mut particle: Particle) {
+        let mut result = particle.pos.settle(&self.grid);
+        if particle.material == Location::Water && matches!(result, SettleResult::Done { .. }) {
+            let side = Point {
+                x: particle.pos.x + particle.dir,
+                y: particle.pos.y,
+            };
+            result = particle.pos.settle_result(side, &self.grid);
+            // Blocked this way too: stay put and try the other direction next step.
+            if matches!(result, SettleResult::Done { .. }) && !particle.reversed {
+                particle.dir = -particle.dir;
+                particle.reversed = true;
+                self.activate(particle);
+                return;
+            }
+        }
+        match result {
+            SettleResult::Moved { new } => {
+                self.vacate(particle.pos);
+                self.grid.set_at(new, particle.material);
+                if new.y != particle.pos.y {
+                    particle.reversed = false;
+                }
+                particle.pos = new;
+                self.activate(particle);
+            }
+            SettleResult::Done { .. } => {}
+            SettleResult::Abyss => {
+                self.vacate(particle.pos);
+                self.lost += 1;
+            }
+        }
+    }
+
+    fn step(&mut self) {
+        self.step += 1;
+        let due = self
+            .removals
+            .iter()
+            .filter(|(step, _)| *step == self.step)
+            .map(|(_, pos)| *pos)
+            .collect_vec();
+        for pos in due {
+            self.remove_block(pos);
+        }
+
+        for ix in 0..self.sources.len() {
+            let Source {
+                at,
+                material,
+                every,
+            } = self.sources[ix];
+            if self.step.is_multiple_of(every) && self.grid.at(at) == Some(Location::Empty) {
+                self.grid.set_at(at, material);
+                self.activate(Particle {
+                    pos: at,
+                    material,
+                    dir: if self.step.is_multiple_of(2) { 1 } else { -1 },
+                    reversed: false,
+                });
+            }
+        }
+
+        // Lower particles move first so they don't block the ones falling behind them.
+        self.pending = std::mem::take(&mut self.particles);
+        self.pending.sort_by_key(|it| it.pos.y);
+        while let Some(particle) = self.pending.pop() {
+            self.moving.remove(&particle.pos);
+            self.move_particle(particle);
+        }
+    }
+}
+
+impl Display for Cave {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Step {} ({} moving, {} lost)",
+            self.step,
+            self.particles.len(),
+            self.lost
+        )?;
+        write!(f, "{}", self.grid)
+    }
+}
+
+const DEMO_ROCKS: &str = "498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9
+490,13 -> 510,13";
+const DEMO_BLOCKS: &str = "499,9 -> 501,9
+497,5 -> 497,5";
+
+fn demo_cave() -> Cave {
+    let rocks: Vec<PointChain> = DEMO_ROCKS.lines().map(|it| it.parse().unwrap()).collect();
+    let mut grid = build_start_grid(&rocks, false);
+    let blocks: Vec<PointChain> = DEMO_BLOCKS.lines().map(|it| it.parse().unwrap()).collect();
+    for chain in &blocks {
+        chain.draw(&mut grid, Location::Block);
+    }
+    let mut cave = Cave::new(grid);
+    cave.sources = vec![
+        Source {
+            at: Point { x: 500, y: 0 },
+            material: Location::Sand,
+            every: 3,
+        },
+        Source {
+            at: Point { x: 496, y: 0 },
+            material: Location::Water,
+            every: 2,
+        },
+    ];
+    cave.removals = vec![(40, Point { x: 497, y: 5 }), (80, Point { x: 500, y: 9 })];
+    cave
+}
+
+fn run_flow(steps: usize, frame_delay: Duration) {
+    let mut cave = demo_cave();
+    for _ in 0..steps {
+        cave.step();
+        print!("\x1b[2J\x1b[H");
+        println!("{cave}");
+        std::thread::sleep(frame_delay);
+    }
+}
+
+const SAND_SOURCE: Point = Point { x: 500, y: 0 };
+
+fn part1(source: Point) {
+    let input = read_input_lines_as::<PointChain>(14);
+    let mut grid = build_start_grid(&input, false);
+    let count = pour_sand_memoized(&mut grid, source);
     println!("Part 1: {count}");
 }
+
+fn part2(source: Point) {
+    let input = read_input_lines_as::<PointChain>(14);
+    let mut grid = build_start_grid(&input, true);
+    let row_by_row = count_reachable_with_floor(&grid, source);
+    let count = pour_sand_memoized(&mut grid, source);
+    println!("Part 2: {count} ({row_by_row} counted row by row)");
+}
+
 fn main() {
-    part1();
-    part2();
+    let args = std::env::args().skip(1).collect_vec();
+    let (source, flow) = match args.iter().map(|it| it.as_str()).collect_vec()[..] {
+        ["source", point] => (point.parse().unwrap(), None),
+        ["flow", steps, delay] => (
+            SAND_SOURCE,
+            Some((steps.parse().unwrap(), delay.parse().unwrap())),
+        ),
+        ["flow", steps] => (SAND_SOURCE, Some((steps.parse().unwrap(), 50))),
+        [] => (SAND_SOURCE, None),
+        _ => panic!("Usage: day14 [source <x,y> | flow <steps> [delay_ms]]"),
+    };
+    part1(source);
+    part2(source);
+    if let Some((steps, delay)) = flow {
+        run_flow(steps, Duration::from_millis(delay));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_solvers_agree(source: Point) {
+        let input = read_input_lines_as::<PointChain>(14);
+        for include_floor in [false, true] {
+            let simulated = pour_sand(&mut build_start_grid(&input, include_floor), source);
+            let memoized = pour_sand_memoized(&mut build_start_grid(&input, include_floor), source);
+            assert_eq!(simulated, memoized);
+            if include_floor {
+                let row_by_row =
+                    count_reachable_with_floor(&build_start_grid(&input, true), source);
+                assert_eq!(u128::from(simulated), row_by_row);
+            }
+        }
+    }
+
+    #[test]
+    fn solvers_agree_from_the_puzzle_source() {
+        assert_solvers_agree(SAND_SOURCE);
+    }
+
+    #[test]
+    fn solvers_agree_from_other_sources() {
+        assert_solvers_agree(Point { x: 3000, y: -50 });
+        assert_solvers_agree(Point { x: 480, y: 5 });
+    }
+
+    #[test]
+    fn solvers_agree_from_below_the_floor() {
+        assert_solvers_agree(Point { x: 500, y: 1000 });
+    }
 }
@@ -1,10 +1,8 @@
-use std::collections::HashSet;
 use std::convert::Infallible;
-use std::ops::RangeInclusive;
 use std::str::FromStr;
 
+use adventofcode2022::interval::IntervalSet;
 use adventofcode2022::read_input_lines_as;
-use itertools::Itertools;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete::{char, digit1, space1};
@@ -76,85 +74,8 @@ impl FromStr for Sensor {
     }
 }
 
-struct RangeSet {
-    ranges: Vec<RangeInclusive<i64>>,
-}
-
-fn try_merge(a: &RangeInclusive<i64>, b: &RangeInclusive<i64>) -> Option<RangeInclusive<i64>> {
-    if a.end() < b.start() || b.end() < a.start() {
-        return None;
-    }
-    if a.end() <= b.end() && a.start() >= b.start() {
-        return Some(b.clone());
-    }
-
-    if b.end() <= a.end() && b.start() >= a.start() {
-        return Some(a.clone());
-    }
-
-    if a.start() <= b.start() && a.end() <= b.end() {
-        return Some(*a.start()..=*b.end());
-    }
-
-    if b.start() <= a.start() && b.end() <= a.end() {
-        return Some(*b.start()..=*a.end());
-    }
-
-    unreachable!("Checked overlap possibilities exhaustively.")
-}
-
-impl RangeSet {
-    fn new() -> RangeSet {
-        RangeSet { ranges: vec![] }
-    }
-
-    fn insert(&mut self, item: RangeInclusive<i64>) {
-        let mut next = vec![];
-        for curr in &self.ranges {
-            if let Some(merged) = try_merge(&item, curr) {
-                next.push(merged);
-            } else {
-                next.push(curr.clone());
-            }
-        }
-        if next.len() == self.ranges.len() {
-            next.push(item);
-        }
-        self.ranges = next;
-        self.merge_self();
-    }
-
-    fn merge_self(&mut self) {
-        self.ranges.sort_by_key(|it| *it.start());
-        let ranges = std::mem::take(&mut self.ranges);
-        let mut last = Some(ranges[0].clone());
-        for ((_, curr0), (ix1, curr1)) in ranges.iter().enumerate().tuple_windows() {
-            if let Some(merged) = try_merge(curr0, curr1) {
-                self.ranges.push(merged);
-                self.ranges
-                    .extend(ranges[(ix1 + 1)..ranges.len()].iter().cloned());
-                return self.merge_self();
-            } else {
-                self.ranges.push(curr0.clone());
-                last = Some(curr1.clone());
-            }
-        }
-
-        if let Some(l) = last {
-            self.ranges.push(l);
-        }
-    }
-
-    fn size(&self) -> i64 {
-        self.ranges
-            .iter()
-            .map(|it| *it.end() - *it.start() + 1)
-            .sum()
-    }
-}
-
-fn count_disallowed_in_row(row: i64, sensors: &[Sensor]) -> usize {
-    let mut all_disallowed = HashSet::new();
+fn count_disallowed_in_row(row: i64, sensors: &[Sensor]) -> u128 {
+    let mut all_disallowed = IntervalSet::new();
     for sensor in sensors {
         let dist = sensor.dist_to_closest();
         let dist_to_row = (sensor.loc.y - row).abs();
@@ -163,20 +84,22 @@ fn count_disallowed_in_row(row: i64, sensors: &[Sensor]) -> usize {
             continue;
         }
         let x_poses = (sensor.loc.x - extra_dist)..=(sensor.loc.x + extra_dist);
-        all_disallowed.extend(x_poses);
+        all_disallowed.insert(x_poses);
     }
 
     for sensor in sensors {
         if sensor.closest_beacon.y == row {
-            all_disallowed.remove(&sensor.closest_beacon.x);
+            let x = sensor.closest_beacon.x;
+            all_disallowed.remove(x..=x);
         }
     }
 
-    all_disallowed.len()
+    all_disallowed.size()
 }
 
 fn find_allowed_in_row(row: i64, sensors: &[Sensor]) -> Option<Point> {
-    let mut all_disallowed = RangeSet::new();
+    let bounds = 0..=4000000;
+    let mut all_disallowed = IntervalSet::new();
     for sensor in sensors {
         let dist = sensor.dist_to_closest();
         let dist_to_row = (sensor.loc.y - row).abs();
@@ -184,27 +107,15 @@ fn find_allowed_in_row(row: i64, sensors: &[Sensor]) -> Option<Point> {
         if extra_dist <= 0 {
             continue;
         }
-        let x_poses = std::cmp::max(sensor.loc.x - extra_dist, 0)
-            ..=std::cmp::min(sensor.loc.x + extra_dist, 4000000);
+        let x_poses = std::cmp::max(sensor.loc.x - extra_dist, *bounds.start())
+            ..=std::cmp::min(sensor.loc.x + extra_dist, *bounds.end());
         all_disallowed.insert(x_poses);
-        if all_disallowed.size() == 4000001 {
-            break;
-        }
     }
 
-    if all_disallowed.size() < 4000001 {
-        assert!(all_disallowed.ranges.len() == 2);
-        if *all_disallowed.ranges[0].start() > 0 {
-            Some(Point { x: 0, y: row })
-        } else {
-            Some(Point {
-                x: *all_disallowed.ranges[0].end() + 1,
-                y: row,
-            })
-        }
-    } else {
-        None
-    }
+    all_disallowed.gaps(bounds).next().map(|gap| Point {
+        x: *gap.start(),
+        y: row,
+    })
 }
 
 fn part1() {
@@ -169,7 +169,7 @@ fn build_inverted(
                     id: from_id.into(),
                     action: Action::Calc {
                         lhs: parent.id.clone(),
-                        rhs: rhs,
+                        rhs,
                         op: Op::Subtract,
                     },
                 })
@@ -194,7 +194,7 @@ fn build_inverted(
                     id: from_id.into(),
                     action: Action::Calc {
                         lhs: parent.id.clone(),
-                        rhs: rhs,
+                        rhs,
                         op: Op::Add,
                     },
                 })
@@ -202,7 +202,7 @@ fn build_inverted(
                 building.push(Monkey {
                     id: from_id.into(),
                     action: Action::Calc {
-                        lhs: lhs,
+                        lhs,
                         rhs: parent.id.clone(),
                         op: Op::Subtract,
                     },
@@ -219,7 +219,7 @@ fn build_inverted(
                     id: from_id.into(),
                     action: Action::Calc {
                         lhs: parent.id.clone(),
-                        rhs: rhs,
+                        rhs,
                         op: Op::Divide,
                     },
                 })
@@ -244,7 +244,7 @@ fn build_inverted(
                     id: from_id.into(),
                     action: Action::Calc {
                         lhs: parent.id.clone(),
-                        rhs: rhs,
+                        rhs,
                         op: Op::Multiply,
                     },
                 })
@@ -252,7 +252,7 @@ fn build_inverted(
                 building.push(Monkey {
                     id: from_id.into(),
                     action: Action::Calc {
-                        lhs: lhs,
+                        lhs,
                         rhs: parent.id.clone(),
                         op: Op::Divide,
                     },
@@ -0,0 +1,267 @@
+use std::fmt::Debug;
+use std::ops::RangeInclusive;
+
+pub trait Discrete: Copy + Ord + Debug {
+    fn checked_succ(self) -> Option<Self>;
+    fn checked_pred(self) -> Option<Self>;
+    fn span(lower: Self, upper: Self) -> u128;
+}
+
+macro_rules! impl_discrete {
+    ($($t:ty),*) => {
+        $(
+            impl Discrete for $t {
+                fn checked_succ(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+
+                fn checked_pred(self) -> Option<Self> {
+                    self.checked_sub(1)
+                }
+
+                fn span(lower: Self, upper: Self) -> u128 {
+                    (upper as i128 - lower as i128 + 1) as u128
+                }
+            }
+        )*
+    };
+}
+
+impl_discrete!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+// True when `upper` and `lower` overlap or are directly adjacent, i.e. upper + 1 >= lower.
+fn reaches<T: Discrete>(upper: T, lower: T) -> bool {
+    match upper.checked_succ() {
+        Some(next) => next >= lower,
+        None => true,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntervalSet<T> {
+    ranges: Vec<RangeInclusive<T>>,
+}
+
+impl<T: Discrete> Default for IntervalSet<T> {
+    fn default() -> Self {
+        IntervalSet::new()
+    }
+}
+
+impl<T: Discrete> IntervalSet<T> {
+    pub fn new() -> IntervalSet<T> {
+        IntervalSet { ranges: vec![] }
+    }
+
+    pub fn single(range: RangeInclusive<T>) -> IntervalSet<T> {
+        let mut set = IntervalSet::new();
+        set.insert(range);
+        set
+    }
+
+    pub fn ranges(&self) -> &[RangeInclusive<T>] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn size(&self) -> u128 {
+        self.ranges
+            .iter()
+            .map(|it| T::span(*it.start(), *it.end()))
+            .sum()
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        let ix = self.ranges.partition_point(|it| *it.end() < value);
+        ix < self.ranges.len() && *self.ranges[ix].start() <= value
+    }
+
+    pub fn insert(&mut self, range: RangeInclusive<T>) {
+        let (lower, upper) = (*range.start(), *range.end());
+        if lower > upper {
+            return;
+        }
+        let first = self.ranges.partition_point(|it| !reaches(*it.end(), lower));
+        let last = self
+            .ranges
+            .partition_point(|it| reaches(upper, *it.start()));
+        let merged = if first < last {
+            std::cmp::min(lower, *self.ranges[first].start())
+                ..=std::cmp::max(upper, *self.ranges[last - 1].end())
+        } else {
+            range
+        };
+        self.ranges.splice(first..last, [merged]);
+    }
+
+    pub fn remove(&mut self, range: RangeInclusive<T>) {
+        let (lower, upper) = (*range.start(), *range.end());
+        if lower > upper {
+            return;
+        }
+        let first = self.ranges.partition_point(|it| *it.end() < lower);
+        let last = self.ranges.partition_point(|it| *it.start() <= upper);
+        if first >= last {
+            return;
+        }
+        let mut remaining = vec![];
+        let head_start = *self.ranges[first].start();
+        if head_start < lower {
+            remaining.push(head_start..=lower.checked_pred().unwrap());
+        }
+        let tail_end = *self.ranges[last - 1].end();
+        if tail_end > upper {
+            remaining.push(upper.checked_succ().unwrap()..=tail_end);
+        }
+        self.ranges.splice(first..last, remaining);
+    }
+
+    pub fn union(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut result = self.clone();
+        for range in &other.ranges {
+            result.insert(range.clone());
+        }
+        result
+    }
+
+    pub fn intersection(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut result = IntervalSet::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            let lower = std::cmp::max(*a.start(), *b.start());
+            let upper = std::cmp::min(*a.end(), *b.end());
+            if lower <= upper {
+                result.ranges.push(lower..=upper);
+            }
+            if a.end() < b.end() {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        result
+    }
+
+    pub fn difference(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut result = self.clone();
+        for range in &other.ranges {
+            result.remove(range.clone());
+        }
+        result
+    }
+
+    pub fn complement(&self, bounds: RangeInclusive<T>) -> IntervalSet<T> {
+        IntervalSet::single(bounds).difference(self)
+    }
+
+    pub fn gaps(&self, bounds: RangeInclusive<T>) -> impl Iterator<Item = RangeInclusive<T>> {
+        self.complement(bounds).ranges.into_iter()
+    }
+
+    pub fn is_superset(&self, other: &IntervalSet<T>) -> bool {
+        other.difference(self).is_empty()
+    }
+
+    pub fn overlaps(&self, other: &IntervalSet<T>) -> bool {
+        !self.intersection(other).is_empty()
+    }
+}
+
+impl<T: Discrete> FromIterator<RangeInclusive<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = RangeInclusive<T>>>(iter: I) -> Self {
+        let mut set = IntervalSet::new();
+        for range in iter {
+            set.insert(range);
+        }
+        set
+    }
+}
+
+impl<T: Discrete> Extend<RangeInclusive<T>> for IntervalSet<T> {
+    fn extend<I: IntoIterator<Item = RangeInclusive<T>>>(&mut self, iter: I) {
+        for range in iter {
+            self.insert(range);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_merges_overlapping_and_adjacent_ranges() {
+        let mut set = IntervalSet::new();
+        set.insert(1..=3);
+        set.insert(7..=9);
+        assert_eq!(set.ranges(), &[1..=3, 7..=9]);
+        set.insert(4..=4);
+        assert_eq!(set.ranges(), &[1..=4, 7..=9]);
+        set.insert(5..=6);
+        assert_eq!(set.ranges(), &[1..=9]);
+        set.insert(0..=12);
+        assert_eq!(set.ranges(), &[0..=12]);
+        let (lower, upper) = (5, 3);
+        set.insert(lower..=upper);
+        assert_eq!(set.ranges(), &[0..=12]);
+    }
+
+    #[test]
+    fn remove_splits_a_range() {
+        let mut set = IntervalSet::single(1..=10);
+        set.remove(4..=6);
+        assert_eq!(set.ranges(), &[1..=3, 7..=10]);
+        assert_eq!(set.size(), 7);
+        assert!(!set.contains(5));
+        set.remove(0..=3);
+        assert_eq!(set.ranges(), &[7..=10]);
+        set.remove(10..=20);
+        assert_eq!(set.ranges(), &[7..=9]);
+    }
+
+    #[test]
+    fn complement_and_gaps_stay_within_bounds() {
+        let set: IntervalSet<i32> = [2..=3, 6..=8, 15..=20].into_iter().collect();
+        assert_eq!(set.complement(0..=10).ranges(), &[0..=1, 4..=5, 9..=10]);
+        assert_eq!(set.gaps(3..=7).collect::<Vec<_>>(), vec![4..=5]);
+        assert_eq!(set.gaps(6..=8).count(), 0);
+    }
+
+    #[test]
+    fn set_operations() {
+        let a: IntervalSet<i32> = [0..=5, 10..=15].into_iter().collect();
+        let b: IntervalSet<i32> = [3..=11].into_iter().collect();
+        assert_eq!(a.union(&b).ranges(), &[0..=15]);
+        assert_eq!(a.intersection(&b).ranges(), &[3..=5, 10..=11]);
+        assert_eq!(a.difference(&b).ranges(), &[0..=2, 12..=15]);
+        assert!(a.overlaps(&b));
+        assert!(a.is_superset(&IntervalSet::single(11..=14)));
+        assert!(!a.is_superset(&b));
+    }
+
+    #[test]
+    fn handles_the_edges_of_the_type() {
+        let mut set = IntervalSet::single(u64::MAX - 1..=u64::MAX);
+        set.insert(0..=u64::MAX - 2);
+        assert_eq!(set.ranges(), &[0..=u64::MAX]);
+        assert_eq!(set.size(), u128::from(u64::MAX) + 1);
+        set.remove(u64::MAX..=u64::MAX);
+        set.remove(0..=0);
+        assert_eq!(set.ranges(), &[1..=u64::MAX - 1]);
+
+        let full = IntervalSet::single(i64::MIN..=i64::MAX);
+        assert_eq!(full.size(), 1 << 64);
+        assert!(full.complement(i64::MIN..=i64::MAX).is_empty());
+        let mut signed = IntervalSet::single(i8::MIN..=i8::MAX);
+        signed.remove(i8::MIN..=-1);
+        assert_eq!(signed.ranges(), &[0..=i8::MAX]);
+    }
+}
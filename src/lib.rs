@@ -1,14 +1,17 @@
+pub mod interval;
+
 use std::fmt::Debug;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+pub fn input_path(day: u8) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("inputs")
+        .join(format!("day{day:02}.txt"))
+}
+
 pub fn read_input(day: u8) -> String {
-    std::fs::read_to_string(
-        Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("inputs")
-            .join(format!("day{day:02}.txt")),
-    )
-    .unwrap()
+    std::fs::read_to_string(input_path(day)).unwrap()
 }
 
 pub fn read_input_lines(day: u8, include_empty: bool) -> Vec<String> {